@@ -0,0 +1,154 @@
+//! Compares hitting a scene through its BVH against testing every item. The
+//! scenes mimic the ones made by `gen_random_balls`, with a configurable
+//! number of balls.
+
+use std::convert::TryInto;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use glam::Vec3;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use raytracer::{
+    cache::Cache,
+    camera::{CameraInitOptions, Projection},
+    geometry::{Geometry, Sphere},
+    item::Item,
+    material::{Lambertian, Material},
+    primitive::Ray3,
+    scene::{Scene, SceneDescription},
+    sky_box::{Cubemap, SkyBox},
+    texture::{Solid, Texture},
+};
+
+const NUM_RAYS: usize = 1024;
+
+/// Creates a scene with a ground sphere and `2 * half_grid` by `2 * half_grid`
+/// small balls around the origin. `half_grid = 6` gives the same layout as
+/// `gen_random_balls`.
+fn random_balls(half_grid: i32) -> Scene {
+    let mut texture_cache = Cache::new();
+    let mut geometry_cache = Cache::new();
+    let mut material_cache = Cache::new();
+    let mut items = vec![];
+
+    let texture_idx = texture_cache.add(
+        "gray",
+        Texture::Solid(Solid {
+            color: Vec3::splat(0.5),
+        }),
+    );
+    let material_idx = material_cache.add(
+        "gray",
+        Material::Lambertian(Lambertian {
+            texture_idx,
+            normal_map_idx: None,
+            emission: None,
+        }),
+    );
+
+    items.push(Item {
+        geometry_idx: geometry_cache.add(
+            "ground",
+            Geometry::Sphere(Sphere {
+                center: Vec3::new(0.0, -1000.0, 0.0),
+                radius: 1000.0,
+            }),
+        ),
+        material_idx,
+        transform: None,
+    });
+
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(1822);
+
+    for a in -half_grid..half_grid {
+        for b in -half_grid..half_grid {
+            let offset = Vec3::new(rng.gen(), 0.0, rng.gen()) * 0.6;
+            items.push(Item {
+                geometry_idx: geometry_cache.add(
+                    format!("rand_geo_{}_{}", a, b),
+                    Geometry::Sphere(Sphere {
+                        center: Vec3::new(a as f32, 0.2, b as f32) + offset,
+                        radius: 0.2,
+                    }),
+                ),
+                material_idx,
+                transform: None,
+            });
+        }
+    }
+
+    SceneDescription {
+        sky_box: SkyBox::Cubemap(Cubemap::all(texture_idx)),
+        camera: CameraInitOptions {
+            pos: Vec3::new(3.0, 1.5, 8.0),
+            look_at: Vec3::new(0.5, 0.0, -1.0),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            vt_fov: 30.0,
+            aspect: 2.0,
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+        .into(),
+        texture_cache,
+        geometry_cache,
+        material_cache,
+        items,
+        models: vec![],
+    }
+    .try_into()
+    .expect("Couldn't create scene")
+}
+
+fn camera_rays(scene: &Scene) -> Vec<Ray3> {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(2281);
+    (0..NUM_RAYS)
+        .map(|_| scene.camera.get_ray(rng.gen(), rng.gen(), &mut rng))
+        .collect()
+}
+
+/// Finds the closest hit by testing every item in the scene.
+fn linear_hit(scene: &Scene, ray: &Ray3) -> Option<f32> {
+    let mut tmax = f32::MAX;
+    let mut closest_t = None;
+    for item in &scene.items {
+        if let Some(hit) = scene.geometry_cache[item.geometry_idx].hit(ray, 0.001, tmax) {
+            tmax = hit.t;
+            closest_t = Some(hit.t);
+        }
+    }
+    closest_t
+}
+
+fn scene_hit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scene_hit");
+
+    for half_grid in [6, 25, 100] {
+        let scene = random_balls(half_grid);
+        let rays = camera_rays(&scene);
+        let num_items = scene.items.len();
+
+        group.bench_with_input(BenchmarkId::new("linear", num_items), &rays, |b, rays| {
+            b.iter(|| {
+                rays.iter()
+                    .filter_map(|ray| linear_hit(&scene, ray))
+                    .count()
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("bvh", num_items), &rays, |b, rays| {
+            b.iter(|| {
+                rays.iter()
+                    .filter_map(|ray| scene.hit(ray, 0.001, f32::MAX))
+                    .count()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, scene_hit);
+criterion_main!(benches);
@@ -0,0 +1,117 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Finds the files (like images and models) referenced by a scene.
+///
+/// Relative paths are looked up in the scene file's directory first, then in
+/// the search paths in order and finally in the current directory.
+#[derive(Clone, Debug, Default)]
+pub struct AssetPaths {
+    pub scene_dir: PathBuf,
+    pub search_paths: Vec<PathBuf>,
+}
+
+impl AssetPaths {
+    /// Returns the first existing file that `path` could refer to. If there's
+    /// none, the path relative to the scene's directory is returned, so that
+    /// errors mention where the file was expected.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path = path.as_ref();
+
+        if path.is_absolute() {
+            return path.to_owned();
+        }
+
+        let in_scene_dir = self.scene_dir.join(path);
+
+        std::iter::once(in_scene_dir.clone())
+            .chain(self.search_paths.iter().map(|dir| dir.join(path)))
+            .chain(std::iter::once(path.to_owned()))
+            .find(|candidate| candidate.exists())
+            .unwrap_or(in_scene_dir)
+    }
+}
+
+/// Returns `path` relative to `base_dir`. Both have to exist. It's `None` if
+/// they don't or if they have nothing in common (like different drives on
+/// Windows).
+pub fn relative_path<P: AsRef<Path>, B: AsRef<Path>>(path: P, base_dir: B) -> Option<PathBuf> {
+    let path = path.as_ref().canonicalize().ok()?;
+    let base_dir = base_dir.as_ref().canonicalize().ok()?;
+
+    let common = path
+        .components()
+        .zip(base_dir.components())
+        .take_while(|(path_component, base_component)| path_component == base_component)
+        .count();
+
+    if common == 0 {
+        return None;
+    }
+
+    let mut relative: PathBuf = base_dir
+        .components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .collect();
+    relative.extend(path.components().skip(common));
+
+    Some(relative)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// Makes `root/scene/other.png`, `root/search/other.png` and
+    /// `root/search/texture.png`.
+    fn asset_dirs(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(name);
+        fs::create_dir_all(root.join("scene")).unwrap();
+        fs::create_dir_all(root.join("search")).unwrap();
+
+        for file in ["search/texture.png", "search/other.png", "scene/other.png"] {
+            fs::write(root.join(file), []).unwrap();
+        }
+
+        root
+    }
+
+    #[test]
+    fn scene_dir_is_searched_before_search_paths() {
+        let root = asset_dirs("raytracer_assets_resolve");
+        let assets = AssetPaths {
+            scene_dir: root.join("scene"),
+            search_paths: vec![root.join("search")],
+        };
+
+        assert_eq!(root.join("scene/other.png"), assets.resolve("other.png"));
+        assert_eq!(
+            root.join("search/texture.png"),
+            assets.resolve("texture.png")
+        );
+        assert_eq!(
+            root.join("scene/missing.png"),
+            assets.resolve("missing.png")
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn relative_path_goes_up_to_the_common_dir() {
+        let root = asset_dirs("raytracer_assets_relative");
+
+        assert_eq!(
+            Some(PathBuf::from("../search/texture.png")),
+            relative_path(root.join("search/texture.png"), root.join("scene"))
+        );
+        assert_eq!(
+            Some(PathBuf::from("other.png")),
+            relative_path(root.join("scene/other.png"), root.join("scene"))
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
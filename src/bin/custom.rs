@@ -1,3 +1,7 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+
 use clap::Parser;
 use raytracer::{ray_tracer::RayTracer, scene::Scene};
 
@@ -7,21 +11,58 @@ use raytracer::{ray_tracer::RayTracer, scene::Scene};
 struct CliArgs {
     #[clap(flatten)]
     ray_tracer: RayTracer,
-    /// The JSON file which contains the scene description.
+    /// The JSON file which contains the scene description. glTF 2.0 files
+    /// (.gltf or .glb) can be rendered directly too.
     #[clap(long)]
     scene: String,
-    /// The desired path of the rendered image. The extension (png/jpg) decides
-    /// the image format.
+    /// Directories to look for textures and models in when the scene refers
+    /// to them by relative paths. The scene's own directory is searched first.
+    #[clap(long = "asset-path")]
+    asset_paths: Vec<PathBuf>,
+    /// The desired path of the rendered image. The extension (png/jpg/exr/hdr)
+    /// decides the image format. OpenEXR and Radiance HDR images hold the
+    /// linear radiance without any tone mapping.
     #[clap(long, default_value = "scene.png")]
     output: String,
+    /// Render progressively and save the samples taken so far to this file
+    /// after every pass.
+    #[clap(long)]
+    checkpoint: Option<String>,
+    /// Continue the render saved in the checkpoint file instead of starting
+    /// over. Samples are added till every pixel has the requested number.
+    #[clap(long, requires = "checkpoint")]
+    resume: bool,
 }
 
 fn main() {
     let args = CliArgs::parse();
+
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: CliArgs) -> Result<(), Box<dyn Error>> {
     let ray_tracer = args.ray_tracer;
-    let scene = Scene::from_json(args.scene).expect("Couldn't read scene");
+    let scene = Scene::load(&args.scene, &args.asset_paths)?;
+
+    let memory = scene.image_memory();
+    if memory.textures > 0 {
+        eprintln!(
+            "{} image textures use {} images ({:.1} MiB)",
+            memory.textures,
+            memory.images,
+            memory.bytes as f32 / (1024.0 * 1024.0)
+        );
+    }
+
+    match args.checkpoint {
+        Some(checkpoint) => {
+            ray_tracer.render_progressively(&scene, &args.output, checkpoint, args.resume)?
+        }
+        None => ray_tracer.render_to_file(&scene, &args.output)?,
+    }
 
-    ray_tracer
-        .render_to_file(&scene, args.output)
-        .expect("Couldn't write image data");
+    Ok(())
 }
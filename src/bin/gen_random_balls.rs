@@ -4,11 +4,11 @@ use rand::{thread_rng, Rng, SeedableRng};
 use rand_xoshiro::Xoshiro256PlusPlus;
 use raytracer::{
     cache::Cache,
-    camera::CameraInitOptions,
+    camera::{CameraInitOptions, Projection},
     geometry::{Geometry, Sphere},
     item::Item,
     material::{Dielectric, Lambertian, Material, Metal},
-    scene::Scene,
+    scene::SceneDescription,
     sky_box::{Cubemap, SkyBox},
     texture::{Image, Solid, Texture},
 };
@@ -25,64 +25,47 @@ struct CliArgs {
     /// for you.
     #[clap(long)]
     seed: Option<u64>,
+    /// The JSON file to write the scene to. Paths to textures are written
+    /// relative to it. If you don't provide one, the scene is printed with
+    /// absolute paths instead.
+    #[clap(long)]
+    output: Option<String>,
 }
 
+/// The sky box textures which come with the repository.
+const TEXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/inputs/textures");
+
 fn main() {
     let args = CliArgs::parse();
     let seed = args.seed.unwrap_or(thread_rng().gen());
     eprintln!("{} is the scene generation seed", seed);
     let scene = setup_scene(seed, args.aspect);
-    let json = serde_json::to_string_pretty(&scene).expect("Couldn't serialize scene");
-    println!("{}", json);
+
+    match args.output {
+        Some(output) => scene.save(output).expect("Couldn't write scene"),
+        None => {
+            let json = serde_json::to_string_pretty(&scene).expect("Couldn't serialize scene");
+            println!("{}", json);
+        }
+    }
+}
+
+fn sky_texture(side: &str) -> Texture {
+    let path = format!("{}/yellowcloud_{}.png", TEXTURE_DIR, side);
+    Texture::Image(Image::load(path).expect("Couldn't load texture"))
 }
 
-fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
+fn setup_scene(scene_seed: u64, aspect: f32) -> SceneDescription {
     let mut texture_cache = Cache::new();
 
-    let mut scene = Scene {
+    let mut scene = SceneDescription {
         sky_box: SkyBox::Cubemap(Cubemap {
-            up_idx: texture_cache.add(
-                "skybox_up",
-                Texture::Image(
-                    Image::load("inputs/textures/yellowcloud_up.png")
-                        .expect("Couldn't load texture"),
-                ),
-            ),
-            dn_idx: texture_cache.add(
-                "skybox_dn",
-                Texture::Image(
-                    Image::load("inputs/textures/yellowcloud_dn.png")
-                        .expect("Couldn't load texture"),
-                ),
-            ),
-            lf_idx: texture_cache.add(
-                "skybox_lf",
-                Texture::Image(
-                    Image::load("inputs/textures/yellowcloud_lf.png")
-                        .expect("Couldn't load texture"),
-                ),
-            ),
-            rt_idx: texture_cache.add(
-                "skybox_rt",
-                Texture::Image(
-                    Image::load("inputs/textures/yellowcloud_rt.png")
-                        .expect("Couldn't load texture"),
-                ),
-            ),
-            ft_idx: texture_cache.add(
-                "skybox_ft",
-                Texture::Image(
-                    Image::load("inputs/textures/yellowcloud_ft.png")
-                        .expect("Couldn't load texture"),
-                ),
-            ),
-            bk_idx: texture_cache.add(
-                "skybox_bk",
-                Texture::Image(
-                    Image::load("inputs/textures/yellowcloud_bk.png")
-                        .expect("Couldn't load texture"),
-                ),
-            ),
+            up_idx: texture_cache.add("skybox_up", sky_texture("up")),
+            dn_idx: texture_cache.add("skybox_dn", sky_texture("dn")),
+            lf_idx: texture_cache.add("skybox_lf", sky_texture("lf")),
+            rt_idx: texture_cache.add("skybox_rt", sky_texture("rt")),
+            ft_idx: texture_cache.add("skybox_ft", sky_texture("ft")),
+            bk_idx: texture_cache.add("skybox_bk", sky_texture("bk")),
         }),
         camera: CameraInitOptions {
             pos: Vec3::new(3.0, 1.5, 8.0),
@@ -90,12 +73,18 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
             vup: Vec3::new(0.0, 1.0, 0.0),
             vt_fov: 30.0,
             aspect,
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
         }
         .into(),
         texture_cache,
         geometry_cache: Cache::new(),
         material_cache: Cache::new(),
         items: Vec::with_capacity(1 + 12 * 12 + 3),
+        models: vec![],
     };
 
     scene.items.push(Item {
@@ -116,8 +105,10 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
                     }),
                 ),
                 normal_map_idx: None,
+                emission: None,
             }),
         ),
+        transform: None,
     });
 
     let mut rng = Xoshiro256PlusPlus::seed_from_u64(scene_seed);
@@ -141,6 +132,7 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
                         }),
                     ),
                     normal_map_idx: None,
+                    emission: None,
                 })
             } else if material_chooser < 0.95 {
                 Material::Metal(Metal {
@@ -156,11 +148,16 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
                     ),
                     normal_map_idx: None,
                     fuzz: 0.5 * rng.gen::<f32>(),
+                    emission: None,
                 })
             } else {
                 Material::Dielectric(Dielectric {
                     ref_idx: 1.5,
                     normal_map_idx: None,
+                    roughness: 0.0,
+                    absorption: None,
+                    dispersion: None,
+                    emission: None,
                 })
             };
 
@@ -175,6 +172,7 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
                 material_idx: scene
                     .material_cache
                     .add(format!("rand_mat_{}_{}", a, b), material),
+                transform: None,
             });
         }
     }
@@ -197,8 +195,10 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
                     }),
                 ),
                 normal_map_idx: None,
+                emission: None,
             }),
         ),
+        transform: None,
     });
 
     scene.items.push(Item {
@@ -214,8 +214,13 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
             Material::Dielectric(Dielectric {
                 ref_idx: 1.5,
                 normal_map_idx: None,
+                roughness: 0.0,
+                absorption: None,
+                dispersion: None,
+                emission: None,
             }),
         ),
+        transform: None,
     });
 
     scene.items.push(Item {
@@ -237,8 +242,10 @@ fn setup_scene(scene_seed: u64, aspect: f32) -> Scene {
                 ),
                 normal_map_idx: None,
                 fuzz: 0.0,
+                emission: None,
             }),
         ),
+        transform: None,
     });
 
     scene
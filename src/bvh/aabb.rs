@@ -1,13 +1,45 @@
-use glam::Vec3;
+use glam::{const_vec3, Vec3};
 
 use crate::primitive::Ray3;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Aabb {
     pub min: Vec3,
     pub max: Vec3,
 }
 
 impl Aabb {
+    /// An inside-out box which contains nothing. Taking its union with any
+    /// other box gives back the other box.
+    pub const EMPTY: Aabb = Aabb {
+        min: const_vec3!([f32::INFINITY; 3]),
+        max: const_vec3!([f32::NEG_INFINITY; 3]),
+    };
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grow(&self, pos: Vec3) -> Aabb {
+        Aabb {
+            min: self.min.min(pos),
+            max: self.max.max(pos),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Returns the surface area of the box. An empty box has no area.
+    pub fn surface_area(&self) -> f32 {
+        let extent = (self.max - self.min).max(Vec3::ZERO);
+        2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+    }
+
     pub fn hit(&self, ray: &Ray3, mut tmin: f32, mut tmax: f32) -> bool {
         for i in [0, 1, 2] {
             let t1 = (self.min[i] - ray.pos[i]) / ray.dir[i];
@@ -39,6 +71,45 @@ mod tests {
     const TMIN: f32 = 1.0;
     const TMAX: f32 = 5.0;
 
+    #[test]
+    fn union_with_empty_is_identity() {
+        let aabb = Aabb {
+            min: Vec3::ZERO,
+            max: Vec3::ONE,
+        };
+        assert_eq!(aabb, Aabb::EMPTY.union(&aabb));
+        assert_eq!(aabb, aabb.union(&Aabb::EMPTY));
+    }
+
+    #[test]
+    fn union_encloses_both_boxes() {
+        let lhs = Aabb {
+            min: Vec3::new(0.0, 2.0, -1.0),
+            max: Vec3::new(1.0, 3.0, 0.0),
+        };
+        let rhs = Aabb {
+            min: Vec3::new(-2.0, 0.0, 4.0),
+            max: Vec3::new(-1.0, 1.0, 5.0),
+        };
+        assert_eq!(
+            Aabb {
+                min: Vec3::new(-2.0, 0.0, -1.0),
+                max: Vec3::new(1.0, 3.0, 5.0),
+            },
+            lhs.union(&rhs)
+        );
+    }
+
+    #[test]
+    fn surface_area_works() {
+        let aabb = Aabb {
+            min: Vec3::ZERO,
+            max: Vec3::new(1.0, 2.0, 3.0),
+        };
+        assert_eq!(22.0, aabb.surface_area());
+        assert_eq!(0.0, Aabb::EMPTY.surface_area());
+    }
+
     #[test]
     fn intersect_works() {
         assert_eq!((2.5, 5.0), intersect(1.0, 5.0, 2.5, 6.0));
@@ -1,3 +1,5 @@
 mod aabb;
+mod tree;
 
 pub use aabb::Aabb;
+pub use tree::Bvh;
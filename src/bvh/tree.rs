@@ -0,0 +1,479 @@
+use glam::Vec3;
+
+use crate::geometry::HitInfo;
+use crate::primitive::Ray3;
+
+use super::Aabb;
+
+/// The number of buckets the centroids are sorted into when looking for the
+/// cheapest split. More buckets find better splits but make building slower.
+const NUM_BINS: usize = 12;
+
+/// Nodes with more items than this are always split, even if the SAH thinks
+/// that a leaf would be cheaper.
+const MAX_LEAF_SIZE: usize = 4;
+
+/// The cost of visiting a node relative to the cost of hitting an item.
+const TRAVERSAL_COST: f32 = 0.125;
+
+/// The size of the traversal stack in `Bvh::hit`. It holds at most one node
+/// per level of the tree plus one.
+const STACK_SIZE: usize = 64;
+
+/// Nodes deeper than this are split at the median instead of where the SAH
+/// says. Median splits halve the items, so no tree over fewer than 2³² items
+/// gets deep enough to overflow the traversal stack.
+const MAX_SAH_DEPTH: usize = 24;
+
+/// A bounding volume hierarchy over a list of items. It only stores the
+/// indexes of the items. Hitting the actual items is left to the caller.
+///
+/// The tree is built using the surface area heuristic (SAH) and is stored
+/// flattened in depth-first order. The left child of an interior node is
+/// always the node right after it.
+pub struct Bvh {
+    nodes: Vec<Node>,
+    item_idxs: Vec<usize>,
+}
+
+struct Node {
+    aabb: Aabb,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Leaf { first: usize, count: usize },
+    Interior { right: usize, axis: usize },
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    aabb: Aabb,
+    count: usize,
+}
+
+impl Bin {
+    const EMPTY: Bin = Bin {
+        aabb: Aabb::EMPTY,
+        count: 0,
+    };
+}
+
+impl Bvh {
+    /// Builds a tree over items whose bounding boxes are given by `aabbs`. The
+    /// position of a box in `aabbs` is the index of the item.
+    pub fn new(aabbs: &[Aabb]) -> Self {
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * aabbs.len()),
+            item_idxs: (0..aabbs.len()).collect(),
+        };
+
+        if !aabbs.is_empty() {
+            let centroids: Vec<Vec3> = aabbs.iter().map(Aabb::centroid).collect();
+            bvh.build(aabbs, &centroids, 0, aabbs.len(), 0);
+        }
+
+        bvh
+    }
+
+    /// Returns the index of the closest item hit by the ray along with its
+    /// hit info.
+    ///
+    /// `hit_item` is called with the index of an item and the current `tmax`.
+    /// It should behave like `Geometry::hit` for that item.
+    pub fn hit<F>(
+        &self,
+        ray: &Ray3,
+        tmin: f32,
+        mut tmax: f32,
+        hit_item: F,
+    ) -> Option<(usize, HitInfo)>
+    where
+        F: Fn(usize, f32) -> Option<HitInfo>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut closest_hit = None;
+        // A fixed array keeps traversal off the heap. It's pushed to and
+        // popped from the end like a Vec.
+        let mut stack = [0; STACK_SIZE];
+        let mut stack_len = 1;
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node_idx = stack[stack_len];
+            let node = &self.nodes[node_idx];
+
+            if !node.aabb.hit(ray, tmin, tmax) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for &item_idx in &self.item_idxs[first..first + count] {
+                        if let Some(hit_info) = hit_item(item_idx, tmax) {
+                            tmax = hit_info.t;
+                            closest_hit = Some((item_idx, hit_info));
+                        }
+                    }
+                }
+                NodeKind::Interior { right, axis } => {
+                    // Visit the nearer child first so that tmax shrinks faster.
+                    // The child pushed last is popped first.
+                    let (far, near) = if ray.dir[axis] < 0.0 {
+                        (node_idx + 1, right)
+                    } else {
+                        (right, node_idx + 1)
+                    };
+                    stack[stack_len] = far;
+                    stack[stack_len + 1] = near;
+                    stack_len += 2;
+                }
+            }
+        }
+
+        closest_hit
+    }
+
+    fn build(
+        &mut self,
+        aabbs: &[Aabb],
+        centroids: &[Vec3],
+        first: usize,
+        count: usize,
+        depth: usize,
+    ) {
+        let node_idx = self.nodes.len();
+        let mut aabb = Aabb::EMPTY;
+        let mut centroid_aabb = Aabb::EMPTY;
+        for &item_idx in &self.item_idxs[first..first + count] {
+            aabb = aabb.union(&aabbs[item_idx]);
+            centroid_aabb = centroid_aabb.grow(centroids[item_idx]);
+        }
+
+        self.nodes.push(Node {
+            aabb,
+            kind: NodeKind::Leaf { first, count },
+        });
+
+        if count == 1 {
+            return;
+        }
+
+        let extent = centroid_aabb.max - centroid_aabb.min;
+        let axis = if extent.x > extent.y && extent.x > extent.z {
+            0
+        } else if extent.y > extent.z {
+            1
+        } else {
+            2
+        };
+
+        // The children are ordered along the axis they were actually split on
+        // so that `hit` can visit the nearer one first.
+        let (mid, split_axis) = if extent[axis] > 0.0 && depth >= MAX_SAH_DEPTH {
+            (self.median_split(centroids, first, count, axis), axis)
+        } else if extent[axis] > 0.0 {
+            match self.find_sah_split(aabbs, centroids, first, count, &aabb, &centroid_aabb) {
+                Some((split_axis, split_pos)) => {
+                    match self.partition(centroids, first, count, split_axis, split_pos) {
+                        // Rounding can leave one side empty. Fall back to the
+                        // median so that the recursion always makes progress.
+                        mid if mid == first || mid == first + count => (
+                            self.median_split(centroids, first, count, split_axis),
+                            split_axis,
+                        ),
+                        mid => (mid, split_axis),
+                    }
+                }
+                None if count > MAX_LEAF_SIZE => {
+                    (self.median_split(centroids, first, count, axis), axis)
+                }
+                None => return,
+            }
+        } else if count > MAX_LEAF_SIZE {
+            // All the centroids are on top of each other. There's no good
+            // split so we just halve the items.
+            (first + count / 2, axis)
+        } else {
+            return;
+        };
+
+        self.build(aabbs, centroids, first, mid - first, depth + 1);
+        let right = self.nodes.len();
+        self.build(aabbs, centroids, mid, first + count - mid, depth + 1);
+
+        self.nodes[node_idx].kind = NodeKind::Interior {
+            right,
+            axis: split_axis,
+        };
+    }
+
+    /// Finds the axis and the position along it which split the items in the
+    /// cheapest way according to the SAH. Returns `None` if not splitting is
+    /// cheaper.
+    fn find_sah_split(
+        &self,
+        aabbs: &[Aabb],
+        centroids: &[Vec3],
+        first: usize,
+        count: usize,
+        aabb: &Aabb,
+        centroid_aabb: &Aabb,
+    ) -> Option<(usize, f32)> {
+        let mut best_cost = f32::INFINITY;
+        let mut best_split = None;
+
+        for axis in [0, 1, 2] {
+            let lo = centroid_aabb.min[axis];
+            let hi = centroid_aabb.max[axis];
+
+            if hi <= lo {
+                continue;
+            }
+
+            let mut bins = [Bin::EMPTY; NUM_BINS];
+            for &item_idx in &self.item_idxs[first..first + count] {
+                let bin = &mut bins[bin_idx(centroids[item_idx][axis], lo, hi)];
+                bin.aabb = bin.aabb.union(&aabbs[item_idx]);
+                bin.count += 1;
+            }
+
+            // Sweep from the right to know the cost of every right half and
+            // then from the left to combine them with every left half.
+            let mut right_costs = [0.0; NUM_BINS];
+            let mut right = Bin::EMPTY;
+            for i in (1..NUM_BINS).rev() {
+                right.aabb = right.aabb.union(&bins[i].aabb);
+                right.count += bins[i].count;
+                right_costs[i] = right.aabb.surface_area() * right.count as f32;
+            }
+
+            let mut left = Bin::EMPTY;
+            for i in 0..NUM_BINS - 1 {
+                left.aabb = left.aabb.union(&bins[i].aabb);
+                left.count += bins[i].count;
+
+                if left.count == 0 || left.count == count {
+                    continue;
+                }
+
+                let cost = TRAVERSAL_COST
+                    + (left.aabb.surface_area() * left.count as f32 + right_costs[i + 1])
+                        / aabb.surface_area();
+
+                if cost < best_cost {
+                    let split_pos = lo + (i + 1) as f32 * (hi - lo) / NUM_BINS as f32;
+                    best_cost = cost;
+                    best_split = Some((axis, split_pos));
+                }
+            }
+        }
+
+        // The cost of a leaf is the number of items in it.
+        if best_cost < count as f32 || count > MAX_LEAF_SIZE {
+            best_split
+        } else {
+            None
+        }
+    }
+
+    /// Moves the items whose centroids lie before `split_pos` to the front of
+    /// the range. Returns the index of the first item in the back.
+    fn partition(
+        &mut self,
+        centroids: &[Vec3],
+        first: usize,
+        count: usize,
+        axis: usize,
+        split_pos: f32,
+    ) -> usize {
+        let mut mid = first;
+        for i in first..first + count {
+            if centroids[self.item_idxs[i]][axis] < split_pos {
+                self.item_idxs.swap(i, mid);
+                mid += 1;
+            }
+        }
+        mid
+    }
+
+    fn median_split(
+        &mut self,
+        centroids: &[Vec3],
+        first: usize,
+        count: usize,
+        axis: usize,
+    ) -> usize {
+        self.item_idxs[first..first + count].sort_unstable_by(|&lhs, &rhs| {
+            centroids[lhs][axis]
+                .partial_cmp(&centroids[rhs][axis])
+                .expect("Centroid is NaN")
+        });
+        first + count / 2
+    }
+}
+
+fn bin_idx(pos: f32, lo: f32, hi: f32) -> usize {
+    let idx = ((pos - lo) / (hi - lo) * NUM_BINS as f32) as usize;
+    idx.min(NUM_BINS - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::geometry::Sphere;
+
+    use super::*;
+
+    fn random_spheres(count: usize) -> Vec<Sphere> {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1822);
+        (0..count)
+            .map(|_| Sphere {
+                center: Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 20.0 - 10.0,
+                radius: rng.gen::<f32>() * 0.5 + 0.1,
+            })
+            .collect()
+    }
+
+    fn brute_force_hit(
+        spheres: &[Sphere],
+        ray: &Ray3,
+        tmin: f32,
+        tmax: f32,
+    ) -> Option<(usize, HitInfo)> {
+        let mut closest_hit: Option<(usize, HitInfo)> = None;
+        for (idx, sphere) in spheres.iter().enumerate() {
+            let tmax = closest_hit.as_ref().map_or(tmax, |(_, hit)| hit.t);
+            if let Some(hit) = sphere.hit(ray, tmin, tmax) {
+                closest_hit = Some((idx, hit));
+            }
+        }
+        closest_hit
+    }
+
+    #[test]
+    fn empty_bvh_hits_nothing() {
+        let bvh = Bvh::new(&[]);
+        let ray = Ray3::new(Vec3::ZERO, Vec3::Z);
+        assert!(bvh
+            .hit(&ray, 0.0, f32::MAX, |_, _| unreachable!())
+            .is_none());
+    }
+
+    #[test]
+    fn bvh_hit_matches_brute_force() {
+        let spheres = random_spheres(500);
+        let aabbs: Vec<Aabb> = spheres.iter().map(Sphere::aabb).collect();
+        let bvh = Bvh::new(&aabbs);
+
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(2281);
+        for _ in 0..1000 {
+            let pos = Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 30.0 - 15.0;
+            let dir = Vec3::new(rng.gen(), rng.gen(), rng.gen()) * 2.0 - 1.0;
+            let ray = Ray3::new(pos, dir);
+
+            let expected = brute_force_hit(&spheres, &ray, 0.001, f32::MAX);
+            let actual = bvh.hit(&ray, 0.001, f32::MAX, |idx, tmax| {
+                spheres[idx].hit(&ray, 0.001, tmax)
+            });
+
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn bvh_handles_items_sharing_a_centroid() {
+        let spheres: Vec<Sphere> = (1..=10)
+            .map(|i| Sphere {
+                center: Vec3::ZERO,
+                radius: i as f32,
+            })
+            .collect();
+        let aabbs: Vec<Aabb> = spheres.iter().map(Sphere::aabb).collect();
+        let bvh = Bvh::new(&aabbs);
+
+        let ray = Ray3::new(Vec3::new(0.0, 0.0, 20.0), -Vec3::Z);
+        let (idx, hit) = bvh
+            .hit(&ray, 0.001, f32::MAX, |idx, tmax| {
+                spheres[idx].hit(&ray, 0.001, tmax)
+            })
+            .unwrap();
+
+        assert_eq!(9, idx);
+        assert_eq!(10.0, hit.t);
+    }
+
+    #[test]
+    fn interior_nodes_store_the_split_axis() {
+        let spheres = random_spheres(500);
+        let aabbs: Vec<Aabb> = spheres.iter().map(Sphere::aabb).collect();
+        let centroids: Vec<Vec3> = aabbs.iter().map(Aabb::centroid).collect();
+        let bvh = Bvh::new(&aabbs);
+
+        // Returns the range of the centroids of all the items under a node
+        // along `axis`.
+        let centroid_range = |node_idx: usize, axis: usize| {
+            let mut lo = f32::INFINITY;
+            let mut hi = f32::NEG_INFINITY;
+            let mut stack = vec![node_idx];
+            while let Some(idx) = stack.pop() {
+                match bvh.nodes[idx].kind {
+                    NodeKind::Leaf { first, count } => {
+                        for &item_idx in &bvh.item_idxs[first..first + count] {
+                            lo = lo.min(centroids[item_idx][axis]);
+                            hi = hi.max(centroids[item_idx][axis]);
+                        }
+                    }
+                    NodeKind::Interior { right, .. } => {
+                        stack.push(idx + 1);
+                        stack.push(right);
+                    }
+                }
+            }
+            (lo, hi)
+        };
+
+        for (node_idx, node) in bvh.nodes.iter().enumerate() {
+            if let NodeKind::Interior { right, axis } = node.kind {
+                let (_, left_hi) = centroid_range(node_idx + 1, axis);
+                let (right_lo, _) = centroid_range(right, axis);
+                assert!(left_hi <= right_lo);
+            }
+        }
+    }
+
+    #[test]
+    fn skewed_items_stay_within_the_traversal_stack() {
+        // Every item is further from the previous one than all the items
+        // before it, which tempts the SAH into peeling them off one by one.
+        let spheres: Vec<Sphere> = (0..200)
+            .map(|i| Sphere {
+                center: Vec3::new(1.5f32.powi(i), 0.0, 0.0),
+                radius: 0.01,
+            })
+            .collect();
+        let aabbs: Vec<Aabb> = spheres.iter().map(Sphere::aabb).collect();
+        let bvh = Bvh::new(&aabbs);
+
+        fn depth(bvh: &Bvh, node_idx: usize) -> usize {
+            match bvh.nodes[node_idx].kind {
+                NodeKind::Leaf { .. } => 0,
+                NodeKind::Interior { right, .. } => {
+                    1 + depth(bvh, node_idx + 1).max(depth(bvh, right))
+                }
+            }
+        }
+        assert!(depth(&bvh, 0) < STACK_SIZE);
+
+        let ray = Ray3::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::X);
+        let actual = bvh.hit(&ray, 0.001, f32::MAX, |idx, tmax| {
+            spheres[idx].hit(&ray, 0.001, tmax)
+        });
+        assert_eq!(brute_force_hit(&spheres, &ray, 0.001, f32::MAX), actual);
+    }
+}
@@ -41,6 +41,31 @@ impl<T> Cache<T> {
     pub fn idx_for(&self, name: &str) -> Option<usize> {
         self.idxs.get(name).copied()
     }
+
+    /// Retrieves the name of the item at the given index. It's slow and only
+    /// meant for reporting errors.
+    pub fn name_of(&self, idx: usize) -> Option<&str> {
+        self.idxs
+            .iter()
+            .find(|(_, item_idx)| **item_idx == idx)
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.items.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items.iter_mut()
+    }
 }
 
 impl<T> Default for Cache<T> {
@@ -88,4 +113,13 @@ mod tests {
         let idx = cache.add("name", 1822);
         assert_eq!(Some(idx), cache.idx_for("name"));
     }
+
+    #[test]
+    fn added_items_name_can_be_retrieved_using_idx() {
+        let mut cache = Cache::new();
+        cache.add("first", 1);
+        let idx = cache.add("second", 2);
+        assert_eq!(Some("second"), cache.name_of(idx));
+        assert_eq!(None, cache.name_of(idx + 1));
+    }
 }
@@ -1,17 +1,27 @@
-use std::f32::consts::PI;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 
-use glam::Vec3;
+use glam::{Vec2, Vec3};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::primitive::Ray3;
+use crate::primitive::{Ray3, RayCone};
 
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(from = "CameraInitOptions", into = "CameraInitOptions")]
 pub struct Camera {
     pub pos: Vec3,
+    /// The lower left corner of the image on the plane in focus.
     pub lower_left_corner: Vec3,
     hz: Vec3,
     vt: Vec3,
+    /// Unit vectors along the image's width and height, and the one pointing
+    /// away from `look_at`.
+    u: Vec3,
+    v: Vec3,
+    w: Vec3,
+    lens_radius: f32,
+    shutter_open: f32,
+    shutter_close: f32,
     init_options: CameraInitOptions,
 }
 
@@ -20,16 +30,138 @@ pub struct CameraInitOptions {
     pub pos: Vec3,
     pub look_at: Vec3,
     pub vup: Vec3,
+    /// Vertical field of view (in degrees) of perspective projections.
     pub vt_fov: f32,
     pub aspect: f32,
+    #[serde(default)]
+    pub projection: Projection,
+    /// Diameter of the camera's lens. Only things at the focus distance are
+    /// sharp, and the wider the lens the blurrier everything else gets. The
+    /// default of 0 makes a pinhole camera which keeps everything sharp. Only
+    /// perspective projections have a lens.
+    #[serde(default)]
+    pub aperture: f32,
+    /// Distance from the camera to the plane in focus. Without it, the camera
+    /// focuses on `look_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub focus_distance: Option<f32>,
+    /// Rays are cast at random times between the shutter opening and closing,
+    /// so things which move in between are blurred. By default, the shutter
+    /// opens and closes at time 0.
+    #[serde(default)]
+    pub shutter_open: f32,
+    #[serde(default)]
+    pub shutter_close: f32,
+}
+
+/// How the camera maps the scene onto the image.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub enum Projection {
+    /// Rays spread out from the camera through a rectangle in front of it.
+    #[default]
+    Perspective,
+    /// Parallel rays along the viewing direction. `width` is the width of
+    /// the view in scene units.
+    Orthographic { width: f32 },
+    /// An equidistant fisheye. The angle between a ray and the viewing
+    /// direction grows linearly with the distance from the image's center.
+    /// `fov` is the angle (in degrees, up to 360) across the image's width.
+    Fisheye { fov: f32 },
+    /// A full 360° panorama. It's the inverse of the mapping used by
+    /// `sky_box::Equirectangular` with `-u`, `v` and `-w` in place of x, y and
+    /// z, where `u`, `v` and `w` (away from `look_at`) are the camera's axes.
+    /// This frame is the camera's turned half a turn about `v`, so the
+    /// viewing direction is at the image's center. A camera at the origin
+    /// looking along +z sees the sky box exactly as it's stored.
+    Equirectangular,
 }
 
 impl Camera {
-    pub fn get_ray(&self, s: f32, t: f32) -> Ray3 {
-        Ray3::new(
-            self.pos,
-            self.lower_left_corner + s * self.hz + t * self.vt - self.pos,
-        )
+    /// Returns a ray through the point (`s`, `t`) of the image, which goes
+    /// from (0, 0) at the lower left corner to (1, 1) at the upper right one.
+    /// The ray starts at a random point on the lens at a random time while
+    /// the shutter is open.
+    pub fn get_ray<R: Rng>(&self, s: f32, t: f32, rng: &mut R) -> Ray3 {
+        let time = if self.shutter_close > self.shutter_open {
+            rng.gen_range(self.shutter_open..self.shutter_close)
+        } else {
+            self.shutter_open
+        };
+
+        let (pos, dir) = match self.init_options.projection {
+            Projection::Perspective => {
+                let lens_pos = self.lens_radius * rand_pos_in_disk(rng);
+                let pos = self.pos + lens_pos.x * self.u + lens_pos.y * self.v;
+                (
+                    pos,
+                    self.lower_left_corner + s * self.hz + t * self.vt - pos,
+                )
+            }
+            Projection::Orthographic { .. } => {
+                (self.lower_left_corner + s * self.hz + t * self.vt, -self.w)
+            }
+            Projection::Fisheye { fov } => {
+                let x = (s - 0.5) * fov.to_radians();
+                let y = (t - 0.5) * fov.to_radians() / self.init_options.aspect;
+                let angle = x.hypot(y);
+                let (sin, cos) = angle.sin_cos();
+                let radial = if angle > 0.0 {
+                    (x * self.u + y * self.v) / angle
+                } else {
+                    Vec3::ZERO
+                };
+
+                (self.pos, sin * radial - cos * self.w)
+            }
+            Projection::Equirectangular => {
+                // Inverts compute_uv_on_sphere_from_normal, whose v goes down,
+                // in the frame of -u, v and -w.
+                let u_angle = TAU * s - PI;
+                let v_angle = PI * (1.0 - t);
+                let latitude = FRAC_PI_2 - v_angle;
+
+                let dir = -latitude.cos() * (u_angle.sin() * self.u + u_angle.cos() * self.w)
+                    + latitude.sin() * self.v;
+                (self.pos, dir)
+            }
+        };
+
+        Ray3::at_time(pos, dir, time)
+    }
+
+    /// Returns the cone which the rays through a pixel fill, for an image
+    /// `canvas_ht` pixels high. Textures are blurred to the cone's footprint.
+    pub fn ray_cone(&self, canvas_ht: u32) -> RayCone {
+        let canvas_ht = canvas_ht as f32;
+
+        match self.init_options.projection {
+            Projection::Perspective => RayCone {
+                width: 0.0,
+                spread: (self.init_options.vt_fov.to_radians() / 2.0).tan() * 2.0 / canvas_ht,
+            },
+            Projection::Orthographic { .. } => RayCone {
+                width: self.vt.length() / canvas_ht,
+                spread: 0.0,
+            },
+            Projection::Fisheye { fov } => RayCone {
+                width: 0.0,
+                spread: fov.to_radians() / self.init_options.aspect / canvas_ht,
+            },
+            Projection::Equirectangular => RayCone {
+                width: 0.0,
+                spread: PI / canvas_ht,
+            },
+        }
+    }
+}
+
+fn rand_pos_in_disk<R: Rng>(rng: &mut R) -> Vec2 {
+    loop {
+        let pos = 2.0 * Vec2::new(rng.gen(), rng.gen()) - Vec2::ONE;
+
+        if pos.length_squared() < 1.0 {
+            return pos;
+        }
     }
 }
 
@@ -44,11 +176,35 @@ impl From<CameraInitOptions> for Camera {
         let u = options.vup.cross(w).normalize();
         let v = w.cross(u);
 
+        let focus_distance = options
+            .focus_distance
+            .unwrap_or_else(|| (options.pos - options.look_at).length());
+
+        // The rectangle which rays go through. Orthographic rays start on it.
+        let (lower_left_corner, hz, vt) = match options.projection {
+            Projection::Orthographic { width } => {
+                let hz = width * u;
+                let vt = width / options.aspect * v;
+                (options.pos - hz / 2.0 - vt / 2.0, hz, vt)
+            }
+            _ => (
+                options.pos - focus_distance * (half_wd * u + half_ht * v + w),
+                2.0 * focus_distance * half_wd * u,
+                2.0 * focus_distance * half_ht * v,
+            ),
+        };
+
         Camera {
             pos: options.pos,
-            lower_left_corner: options.pos - half_wd * u - half_ht * v - w,
-            hz: 2.0 * half_wd * u,
-            vt: 2.0 * half_ht * v,
+            lower_left_corner,
+            hz,
+            vt,
+            u,
+            v,
+            w,
+            lens_radius: options.aperture / 2.0,
+            shutter_open: options.shutter_open,
+            shutter_close: options.shutter_close,
             init_options: options,
         }
     }
@@ -59,3 +215,140 @@ impl From<Camera> for CameraInitOptions {
         camera.init_options
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::util::compute_uv_on_sphere_from_normal;
+
+    use super::*;
+
+    fn camera(aperture: f32, focus_distance: Option<f32>) -> Camera {
+        CameraInitOptions {
+            pos: Vec3::new(0.0, 0.0, 5.0),
+            look_at: Vec3::ZERO,
+            vup: Vec3::Y,
+            vt_fov: 60.0,
+            aspect: 2.0,
+            projection: Projection::Perspective,
+            aperture,
+            focus_distance,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+        .into()
+    }
+
+    #[test]
+    fn pinhole_camera_rays_start_at_its_position() {
+        let camera = camera(0.0, None);
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        for _ in 0..10 {
+            let ray = camera.get_ray(0.3, 0.8, &mut rng);
+            assert_eq!(Vec3::new(0.0, 0.0, 5.0), ray.pos);
+        }
+    }
+
+    #[test]
+    fn thin_lens_rays_meet_on_the_plane_in_focus() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        // Autofocus on look_at and an explicit focus distance.
+        for (focus_distance, focus_z) in [(None, 0.0), (Some(2.0), 3.0)] {
+            let camera = camera(0.5, focus_distance);
+            let focus_point = |ray: Ray3| ray.point_at_param((focus_z - ray.pos.z) / ray.dir.z);
+
+            let first = focus_point(camera.get_ray(0.2, 0.7, &mut rng));
+            for _ in 0..10 {
+                let ray = camera.get_ray(0.2, 0.7, &mut rng);
+                assert_ne!(camera.pos, ray.pos);
+                assert!(focus_point(ray).abs_diff_eq(first, 1e-4));
+            }
+        }
+    }
+
+    fn camera_with(projection: Projection) -> Camera {
+        CameraInitOptions {
+            projection,
+            ..camera(0.0, None).into()
+        }
+        .into()
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel_and_span_the_width() {
+        let camera = camera_with(Projection::Orthographic { width: 4.0 });
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        let left = camera.get_ray(0.0, 0.5, &mut rng);
+        let right = camera.get_ray(1.0, 0.5, &mut rng);
+
+        assert_eq!(-Vec3::Z, left.dir);
+        assert_eq!(-Vec3::Z, right.dir);
+        assert!(left.pos.abs_diff_eq(Vec3::new(-2.0, 0.0, 5.0), 1e-6));
+        assert!(right.pos.abs_diff_eq(Vec3::new(2.0, 0.0, 5.0), 1e-6));
+    }
+
+    #[test]
+    fn fisheye_angle_grows_linearly_from_the_center() {
+        let camera = camera_with(Projection::Fisheye { fov: 180.0 });
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        let angle = |s, t| {
+            let dir = camera
+                .get_ray(s, t, &mut Xoshiro256PlusPlus::seed_from_u64(0))
+                .dir;
+            dir.angle_between(-Vec3::Z).to_degrees()
+        };
+
+        assert!(camera
+            .get_ray(0.5, 0.5, &mut rng)
+            .dir
+            .abs_diff_eq(-Vec3::Z, 1e-6));
+        assert!((angle(1.0, 0.5) - 90.0).abs() < 1e-3);
+        assert!((angle(0.75, 0.5) - 45.0).abs() < 1e-3);
+        // The aspect ratio is 2, so the top edge is half as far as the right.
+        assert!((angle(0.5, 1.0) - 45.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn equirectangular_is_the_inverse_of_the_sky_box_mapping() {
+        // This camera's -u, v and -w are the x, y and z axes.
+        let camera: Camera = CameraInitOptions {
+            pos: Vec3::ZERO,
+            look_at: Vec3::Z,
+            projection: Projection::Equirectangular,
+            ..camera(0.0, None).into()
+        }
+        .into();
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        for _ in 0..20 {
+            let (s, t): (f32, f32) = (rng.gen(), rng.gen());
+            let dir = camera.get_ray(s, t, &mut rng).dir;
+            let (u, v) = compute_uv_on_sphere_from_normal(dir.normalize());
+
+            assert!((u - s).abs() < 1e-4, "{} != {}", u, s);
+            assert!((v - (1.0 - t)).abs() < 1e-4, "{} != {}", v, 1.0 - t);
+        }
+    }
+
+    #[test]
+    fn equirectangular_center_looks_at_look_at() {
+        let camera = camera_with(Projection::Equirectangular);
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        let dir = camera.get_ray(0.5, 0.5, &mut rng).dir;
+        assert!(dir.normalize().abs_diff_eq(-Vec3::Z, 1e-6), "{}", dir);
+    }
+
+    #[test]
+    fn old_camera_json_loads_as_a_pinhole_camera() {
+        let json = r#"{"pos": [0, 0, 5], "look_at": [0, 0, 0], "vup": [0, 1, 0], "vt_fov": 60, "aspect": 2}"#;
+        let camera: Camera = serde_json::from_str(json).unwrap();
+        assert_eq!(0.0, camera.lens_radius);
+    }
+}
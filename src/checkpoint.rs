@@ -0,0 +1,169 @@
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use glam::Vec3;
+
+use crate::scene::Scene;
+
+/// Identifies checkpoint files. The last byte is the version of the format.
+const MAGIC: [u8; 8] = *b"RTCKPT\0\x01";
+
+/// Size of the header: magic, scene hash, width, height and sample count.
+const HEADER_LEN: usize = 8 + 8 + 4 + 4 + 4;
+
+/// The state of a progressive render. It holds the sum of the radiance of all
+/// the samples taken so far for every pixel, so more samples can be added to
+/// it at any time.
+///
+/// Checkpoints are saved as a small header followed by the sums as little
+/// endian floats.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// Identifies the scene and the settings the samples were taken with.
+    /// Samples of different scenes mustn't be mixed.
+    pub scene_hash: u64,
+    pub canvas_wd: u32,
+    pub canvas_ht: u32,
+    /// The number of samples taken per pixel.
+    pub num_samples: u32,
+    /// Sums of the samples of every pixel, row by row from the top.
+    pub sums: Vec<Vec3>,
+}
+
+impl Checkpoint {
+    pub fn new(scene_hash: u64, canvas_wd: u32, canvas_ht: u32) -> Self {
+        Self {
+            scene_hash,
+            canvas_wd,
+            canvas_ht,
+            num_samples: 0,
+            sums: vec![Vec3::ZERO; canvas_wd as usize * canvas_ht as usize],
+        }
+    }
+
+    /// Adds the sums of a pass which took `num_samples` samples per pixel.
+    pub fn add(&mut self, sums: &[Vec3], num_samples: u32) {
+        assert_eq!(self.sums.len(), sums.len(), "Pass has a different size");
+
+        for (sum, pass_sum) in self.sums.iter_mut().zip(sums) {
+            *sum += *pass_sum;
+        }
+        self.num_samples += num_samples;
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        if bytes.len() < HEADER_LEN || bytes[..8] != MAGIC {
+            return Err(invalid("Not a checkpoint file"));
+        }
+
+        let u32_at = |idx: usize| u32::from_le_bytes(bytes[idx..idx + 4].try_into().unwrap());
+        let scene_hash = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let (canvas_wd, canvas_ht, num_samples) = (u32_at(16), u32_at(20), u32_at(24));
+
+        let num_floats = 3 * canvas_wd as usize * canvas_ht as usize;
+        if bytes.len() != HEADER_LEN + 4 * num_floats {
+            return Err(invalid("Checkpoint file is truncated"));
+        }
+
+        let floats: Vec<f32> = bytes[HEADER_LEN..]
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            scene_hash,
+            canvas_wd,
+            canvas_ht,
+            num_samples,
+            sums: floats.chunks_exact(3).map(Vec3::from_slice).collect(),
+        })
+    }
+
+    /// Writes to a temporary file first and then renames it, so an earlier
+    /// checkpoint isn't lost if the render is killed while saving.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&self.scene_hash.to_le_bytes())?;
+        writer.write_all(&self.canvas_wd.to_le_bytes())?;
+        writer.write_all(&self.canvas_ht.to_le_bytes())?;
+        writer.write_all(&self.num_samples.to_le_bytes())?;
+
+        for sum in &self.sums {
+            for component in sum.to_array() {
+                writer.write_all(&component.to_le_bytes())?;
+            }
+        }
+
+        writer.into_inner()?.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+/// Hashes the scene along with any other settings which change the samples.
+///
+/// The scene is hashed through its JSON form, whose map keys are sorted. So
+/// the hash is the same across runs. Files referenced by the scene (like
+/// images) are hashed by path only.
+pub fn hash_scene(scene: &Scene, settings: &[u64]) -> u64 {
+    let json = serde_json::to_value(scene)
+        .expect("Couldn't serialize scene")
+        .to_string();
+
+    settings
+        .iter()
+        .flat_map(|setting| setting.to_le_bytes())
+        .chain(json.bytes())
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+// Constants of the 64 bit FNV-1a hash. Unlike the standard library's hasher,
+// it's guaranteed to stay the same between Rust releases.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checkpoint_round_trips_through_a_file() {
+        let mut checkpoint = Checkpoint::new(42, 2, 3);
+        let pass: Vec<Vec3> = (0..6).map(|i| Vec3::splat(i as f32 * 0.5)).collect();
+        checkpoint.add(&pass, 4);
+        checkpoint.add(&pass, 4);
+
+        let path = std::env::temp_dir().join("raytracer_checkpoint_round_trip.ckpt");
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(checkpoint, loaded);
+        assert_eq!(8, loaded.num_samples);
+        assert_eq!(Vec3::splat(5.0), loaded.sums[5]);
+    }
+
+    #[test]
+    fn truncated_checkpoint_is_rejected() {
+        let path = std::env::temp_dir().join("raytracer_checkpoint_truncated.ckpt");
+        Checkpoint::new(42, 2, 3).save(&path).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let err = Checkpoint::load(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
+
+use image::ImageError;
+
+use crate::validation::ValidationError;
+
+/// Everything that can go wrong while loading or saving a scene.
+#[derive(Debug)]
+pub enum Error {
+    /// A file couldn't be read.
+    Io { path: PathBuf, source: io::Error },
+    /// The scene file isn't valid JSON or doesn't describe a scene. Lines and
+    /// columns start at 1. They're 0 if the position isn't known.
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A model file (like an OBJ or glTF file) couldn't be imported.
+    InvalidModel { path: PathBuf, message: String },
+    /// The file of an image texture doesn't exist.
+    MissingTexture { path: PathBuf },
+    /// An image texture couldn't be decoded.
+    UnsupportedImageFormat { path: PathBuf, message: String },
+    /// Some indices or names don't point to anything in the scene's caches.
+    InvalidReference(ValidationError),
+    /// The scene uses an image which is stored inside another file (like a
+    /// glTF file) and can't be written to JSON.
+    EmbeddedImage { path: PathBuf },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io<P: Into<PathBuf>>(path: P, source: io::Error) -> Self {
+        Self::Io {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub fn parse<P: Into<PathBuf>>(path: P, err: serde_json::Error) -> Self {
+        Self::Parse {
+            path: path.into(),
+            line: err.line(),
+            column: err.column(),
+            message: err.to_string(),
+        }
+    }
+
+    pub fn invalid_model<P: Into<PathBuf>, E: Display>(path: P, err: E) -> Self {
+        Self::InvalidModel {
+            path: path.into(),
+            message: err.to_string(),
+        }
+    }
+
+    /// Sorts an error of the `image` crate into missing files, unreadable
+    /// files and everything else, which is blamed on the image format.
+    pub fn image<P: Into<PathBuf>>(path: P, err: ImageError) -> Self {
+        let path = path.into();
+
+        match err {
+            ImageError::IoError(source) if source.kind() == io::ErrorKind::NotFound => {
+                Self::MissingTexture { path }
+            }
+            ImageError::IoError(source) => Self::Io { path, source },
+            err => Self::UnsupportedImageFormat {
+                path,
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            // serde_json's messages end with the position already.
+            Self::Parse { path, message, .. } => write!(f, "{}: {}", path.display(), message),
+            Self::InvalidModel { path, message } => write!(f, "{}: {}", path.display(), message),
+            Self::MissingTexture { path } => {
+                write!(f, "{}: texture file doesn't exist", path.display())
+            }
+            Self::UnsupportedImageFormat { path, message } => {
+                write!(f, "{}: unsupported image: {}", path.display(), message)
+            }
+            Self::InvalidReference(err) => err.fmt(f),
+            Self::EmbeddedImage { path } => write!(
+                f,
+                "{}: embedded images can't be saved, extract them to files first",
+                path.display()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ValidationError> for Error {
+    fn from(err: ValidationError) -> Self {
+        Self::InvalidReference(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_keeps_its_position() {
+        let json_err = serde_json::from_str::<Vec<u32>>("[1,\n 2,\n x]").unwrap_err();
+
+        match Error::parse("scene.json", json_err) {
+            Error::Parse { line, column, .. } => assert_eq!((3, 2), (line, column)),
+            err => panic!("Expected a parse error, got {:?}", err),
+        }
+    }
+
+    #[test]
+    fn missing_image_is_a_missing_texture() {
+        let err = image::open("does/not/exist.png").unwrap_err();
+
+        assert!(matches!(
+            Error::image("does/not/exist.png", err),
+            Error::MissingTexture { .. }
+        ));
+    }
+}
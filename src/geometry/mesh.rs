@@ -0,0 +1,314 @@
+use std::path::Path;
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::bvh::{Aabb, Bvh};
+use crate::error::{Error, Result};
+use crate::primitive::Ray3;
+
+use super::{HitInfo, Triangle, Vertex};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MeshSource {
+    Obj {
+        path: String,
+        #[serde(default)]
+        material: Option<String>,
+    },
+    Triangles {
+        triangles: Vec<Triangle>,
+    },
+}
+
+/// A triangle mesh. It's either loaded from a Wavefront OBJ file or built in
+/// memory (by an importer for example). Meshes loaded from OBJ files are
+/// serialized as a path while others are serialized as a list of triangles.
+///
+/// Meshes read from JSON don't load their OBJ file. That's up to the scene,
+/// which knows where to look for it.
+#[derive(Deserialize)]
+#[serde(from = "MeshSource")]
+pub struct Mesh {
+    /// The OBJ file the mesh was (or will be) loaded from.
+    pub path: Option<String>,
+    /// If present, only the faces which use the MTL material with this name
+    /// are loaded. An empty name selects the faces without a material.
+    pub material: Option<String>,
+    triangles: Vec<Triangle>,
+    bvh: Bvh,
+    loaded: bool,
+}
+
+impl Mesh {
+    pub fn new(triangles: Vec<Triangle>) -> Self {
+        let aabbs: Vec<Aabb> = triangles.iter().map(Triangle::aabb).collect();
+        Mesh {
+            path: None,
+            material: None,
+            triangles,
+            bvh: Bvh::new(&aabbs),
+            loaded: true,
+        }
+    }
+
+    /// Loads the faces of an OBJ file. A missing MTL file isn't an error. The
+    /// faces are then treated as if they had no material.
+    pub fn load<P: AsRef<Path>>(path: P, material: Option<&str>) -> Result<Self> {
+        let path = path.as_ref();
+        let (models, materials) = load_obj(path)?;
+
+        Mesh::from_obj(path, material, &models, &materials)
+    }
+
+    /// Like `load` but with the models and materials of an OBJ file which was
+    /// already parsed, so that meshes of the same file can share them.
+    pub(crate) fn from_obj(
+        path: &Path,
+        material: Option<&str>,
+        models: &[tobj::Model],
+        materials: &[tobj::Material],
+    ) -> Result<Self> {
+        let models = models.iter().filter(|model| match material {
+            Some(name) => obj_material_name(model, materials) == name,
+            None => true,
+        });
+
+        Mesh::from_obj_models(path, material, models)
+    }
+
+    /// Builds a mesh out of models which were already loaded from the OBJ file
+    /// at `path`. `material` is only recorded so that the mesh serializes back
+    /// to the same faces.
+    pub(crate) fn from_obj_models<'a, I>(
+        path: &Path,
+        material: Option<&str>,
+        models: I,
+    ) -> Result<Self>
+    where
+        I: IntoIterator<Item = &'a tobj::Model>,
+    {
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| Error::invalid_model(path, "Non UTF-8 path"))?;
+
+        let triangles: Vec<Triangle> = models
+            .into_iter()
+            .flat_map(|model| IndexedTriangles::from(&model.mesh).triangles())
+            .collect();
+
+        Ok(Mesh {
+            path: Some(path_str.to_owned()),
+            material: material.map(str::to_owned),
+            ..Mesh::new(triangles)
+        })
+    }
+
+    /// Whether the mesh has its triangles. It's only false for meshes read
+    /// from JSON until their OBJ file is loaded.
+    pub fn is_loaded(&self) -> bool {
+        self.loaded
+    }
+
+    pub fn hit(&self, ray: &Ray3, tmin: f32, tmax: f32) -> Option<HitInfo> {
+        self.bvh
+            .hit(ray, tmin, tmax, |idx, tmax| {
+                self.triangles[idx].hit(ray, tmin, tmax)
+            })
+            .map(|(_, hit_info)| hit_info)
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        self.triangles
+            .iter()
+            .fold(Aabb::EMPTY, |aabb, triangle| aabb.union(&triangle.aabb()))
+    }
+}
+
+impl From<MeshSource> for Mesh {
+    fn from(source: MeshSource) -> Self {
+        match source {
+            MeshSource::Obj { path, material } => Mesh {
+                path: Some(path),
+                material,
+                triangles: vec![],
+                bvh: Bvh::new(&[]),
+                loaded: false,
+            },
+            MeshSource::Triangles { triangles } => Mesh::new(triangles),
+        }
+    }
+}
+
+impl Serialize for Mesh {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum MeshSourceRef<'a> {
+            Obj {
+                path: &'a str,
+                #[serde(skip_serializing_if = "Option::is_none")]
+                material: &'a Option<String>,
+            },
+            Triangles {
+                triangles: &'a [Triangle],
+            },
+        }
+
+        match &self.path {
+            Some(path) => MeshSourceRef::Obj {
+                path,
+                material: &self.material,
+            },
+            None => MeshSourceRef::Triangles {
+                triangles: &self.triangles,
+            },
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Parses an OBJ file along with its MTL file. A missing MTL file gives no
+/// materials.
+pub(crate) fn load_obj(path: &Path) -> Result<(Vec<tobj::Model>, Vec<tobj::Material>)> {
+    let (models, materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS)
+        .map_err(|err| Error::invalid_model(path, err))?;
+
+    Ok((models, materials.unwrap_or_default()))
+}
+
+/// Returns the name of the MTL material used by an OBJ model. Models without a
+/// material, or whose material couldn't be loaded, get an empty name.
+pub(crate) fn obj_material_name<'a>(
+    model: &tobj::Model,
+    materials: &'a [tobj::Material],
+) -> &'a str {
+    model
+        .mesh
+        .material_id
+        .and_then(|id| materials.get(id))
+        .map_or("", |mtl| mtl.name.as_str())
+}
+
+/// The vertex attributes and indices of a triangle list, as stored by most
+/// mesh file formats. Missing attributes are left empty.
+#[derive(Default)]
+pub(crate) struct IndexedTriangles {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub tangents: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+impl IndexedTriangles {
+    /// Converts the list into triangles. Missing normals are replaced by face
+    /// normals. Missing tangents are derived from the UVs.
+    pub fn triangles(&self) -> Vec<Triangle> {
+        let uv = |i: usize| self.uvs.get(i).copied().unwrap_or(Vec2::ZERO);
+
+        let mut tangents = self.tangents.clone();
+
+        if tangents.len() != self.positions.len() {
+            tangents = vec![Vec3::ZERO; self.positions.len()];
+
+            for face in self.indices.chunks_exact(3) {
+                let [a, b, c] = [face[0] as usize, face[1] as usize, face[2] as usize];
+                let pos = [self.positions[a], self.positions[b], self.positions[c]];
+                let tangent = face_tangent(pos, [uv(a), uv(b), uv(c)]);
+
+                for i in [a, b, c] {
+                    tangents[i] += tangent;
+                }
+            }
+        }
+
+        self.indices
+            .chunks_exact(3)
+            .map(|face| {
+                let [a, b, c] = [face[0] as usize, face[1] as usize, face[2] as usize];
+                let face_normal = (self.positions[b] - self.positions[a])
+                    .cross(self.positions[c] - self.positions[a])
+                    .normalize();
+
+                let vertex = |i: usize| Vertex {
+                    pos: self.positions[i],
+                    normal: self.normals.get(i).copied().unwrap_or(face_normal),
+                    uv: uv(i),
+                    tangent: tangents[i],
+                };
+
+                Triangle {
+                    vertices: [vertex(a), vertex(b), vertex(c)],
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<&tobj::Mesh> for IndexedTriangles {
+    fn from(mesh: &tobj::Mesh) -> Self {
+        IndexedTriangles {
+            positions: mesh
+                .positions
+                .chunks_exact(3)
+                .map(Vec3::from_slice)
+                .collect(),
+            normals: mesh.normals.chunks_exact(3).map(Vec3::from_slice).collect(),
+            // OBJ's v goes up while ours goes down.
+            uvs: mesh
+                .texcoords
+                .chunks_exact(2)
+                .map(|uv| Vec2::new(uv[0], 1.0 - uv[1]))
+                .collect(),
+            tangents: vec![],
+            indices: mesh.indices.clone(),
+        }
+    }
+}
+
+/// Returns the direction in which u increases along a face. Falls back to an
+/// edge of the face when its UVs are degenerate.
+fn face_tangent(pos: [Vec3; 3], uv: [Vec2; 3]) -> Vec3 {
+    let e1 = pos[1] - pos[0];
+    let e2 = pos[2] - pos[0];
+    let duv1 = uv[1] - uv[0];
+    let duv2 = uv[2] - uv[0];
+
+    let det = duv1.x * duv2.y - duv2.x * duv1.y;
+
+    if det.abs() < f32::EPSILON {
+        e1
+    } else {
+        (e1 * duv2.y - e2 * duv1.y) / det
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn face_tangent_points_along_increasing_u() {
+        let pos = [Vec3::ZERO, 2.0 * Vec3::Y, 2.0 * Vec3::Z];
+        let uv = [Vec2::ZERO, Vec2::new(0.0, 1.0), Vec2::new(1.0, 0.0)];
+        assert_eq!(Vec3::new(0.0, 0.0, 2.0), face_tangent(pos, uv));
+    }
+
+    #[test]
+    fn mesh_built_in_memory_round_trips_through_json() {
+        let mesh = Mesh::new(vec![Triangle::flat(Vec3::ZERO, Vec3::X, Vec3::Y)]);
+        let json = serde_json::to_string(&mesh).unwrap();
+        let mesh: Mesh = serde_json::from_str(&json).unwrap();
+
+        let ray = Ray3::new(Vec3::new(0.25, 0.25, 1.0), -Vec3::Z);
+        assert_eq!(1.0, mesh.hit(&ray, 0.0, 2.0).unwrap().t);
+    }
+
+    #[test]
+    fn face_tangent_falls_back_to_an_edge_without_uvs() {
+        let pos = [Vec3::ZERO, Vec3::Y, Vec3::Z];
+        assert_eq!(Vec3::Y, face_tangent(pos, [Vec2::ZERO; 3]));
+    }
+}
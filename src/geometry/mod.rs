@@ -1,18 +1,30 @@
+mod mesh;
+mod moving_sphere;
 mod plane;
 mod sphere;
+mod triangle;
 
 use crate::bvh::Aabb;
 use crate::primitive::Ray3;
+use crate::texture::TexCoord;
 
 use glam::{Mat3, Vec3};
+pub use mesh::Mesh;
+pub(crate) use mesh::{load_obj, obj_material_name, IndexedTriangles};
+pub use moving_sphere::{Keyframe, MovingSphere};
 pub use plane::Plane;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 pub use sphere::Sphere;
+pub use triangle::{Triangle, Vertex};
 
 #[derive(Serialize, Deserialize)]
 pub enum Geometry {
+    Mesh(Mesh),
+    MovingSphere(MovingSphere),
     Plane(Plane),
     Sphere(Sphere),
+    Triangle(Triangle),
 }
 
 /// A struct for holding the tangent, bitangent, and the normal vectors. These
@@ -52,20 +64,79 @@ pub struct HitInfo {
     pub v: f32,
     pub pos: Vec3,
     pub tbn: Tbn3,
+    /// The area in the UV space covered by a unit of the surface's area
+    /// around the hit. It's 0 if the surface has no UVs.
+    pub uv_density: f32,
+}
+
+impl HitInfo {
+    /// Returns where the surface's textures are looked up for a hit by
+    /// `ray`. The lookup covers the part of the surface inside the ray's cone.
+    pub fn tex_coord(&self, ray: &Ray3) -> TexCoord {
+        let dir_len = ray.dir.length();
+        let width = ray.cone.width_at(self.t * dir_len);
+        // The cone is stretched along surfaces which it hits at grazing angles.
+        let cos = (self.tbn.n.dot(ray.dir) / dir_len).abs().max(MIN_COS);
+
+        TexCoord {
+            u: self.u,
+            v: self.v,
+            footprint: width * self.uv_density.sqrt() / cos,
+            pos: self.pos,
+        }
+    }
+}
+
+/// Keeps footprints finite for rays which barely graze surfaces.
+const MIN_COS: f32 = 0.01;
+
+/// A point picked on the surface of a geometry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SurfaceSample {
+    pub pos: Vec3,
+    pub normal: Vec3,
+    /// The probability density of picking the point per unit area.
+    pub pdf: f32,
 }
 
 impl Geometry {
     pub fn hit(&self, ray: &Ray3, tmin: f32, tmax: f32) -> Option<HitInfo> {
         match self {
+            Geometry::Mesh(mesh) => mesh.hit(ray, tmin, tmax),
+            Geometry::MovingSphere(sphere) => sphere.hit(ray, tmin, tmax),
             Geometry::Plane(plane) => plane.hit(ray, tmin, tmax),
             Geometry::Sphere(sphere) => sphere.hit(ray, tmin, tmax),
+            Geometry::Triangle(triangle) => triangle.hit(ray, tmin, tmax),
         }
     }
 
-    fn aabb(&self) -> Aabb {
+    pub(crate) fn aabb(&self) -> Aabb {
         match self {
+            Geometry::Mesh(mesh) => mesh.aabb(),
+            Geometry::MovingSphere(sphere) => sphere.aabb(),
             Geometry::Plane(plane) => plane.aabb(),
             Geometry::Sphere(sphere) => sphere.aabb(),
+            Geometry::Triangle(triangle) => triangle.aabb(),
+        }
+    }
+
+    /// Picks a point uniformly on the surface. Only planes and spheres can be
+    /// sampled for now.
+    pub(crate) fn sample_surface<R: Rng>(&self, rng: &mut R) -> Option<SurfaceSample> {
+        match self {
+            Geometry::Plane(plane) => Some(plane.sample_surface(rng)),
+            Geometry::Sphere(sphere) => Some(sphere.sample_surface(rng)),
+            Geometry::Mesh(_) | Geometry::MovingSphere(_) | Geometry::Triangle(_) => None,
+        }
+    }
+
+    /// The probability density (per unit area) with which `sample_surface`
+    /// picks any point. It's 0 for geometries which can't be sampled.
+    pub(crate) fn surface_pdf(&self) -> f32 {
+        match self {
+            Geometry::Plane(plane) => 1.0 / plane.area(),
+            Geometry::Sphere(sphere) => 1.0 / sphere.area(),
+            Geometry::Mesh(_) | Geometry::MovingSphere(_) | Geometry::Triangle(_) => 0.0,
         }
     }
 }
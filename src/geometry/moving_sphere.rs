@@ -0,0 +1,171 @@
+use std::convert::TryFrom;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::bvh::Aabb;
+use crate::primitive::Ray3;
+
+use super::{HitInfo, Sphere};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    pub center: Vec3,
+}
+
+#[derive(Deserialize)]
+struct MovingSphereSource {
+    keyframes: Vec<Keyframe>,
+    radius: f32,
+}
+
+/// A sphere whose center moves through a list of keyframes. The center moves
+/// in a straight line between consecutive keyframes. It stays at the first
+/// (last) keyframe before (after) the keyframes' times.
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "MovingSphereSource")]
+pub struct MovingSphere {
+    /// Keyframes sorted by their time. There must be at least one. Spheres
+    /// read from JSON are checked for both.
+    pub keyframes: Vec<Keyframe>,
+    pub radius: f32,
+}
+
+impl MovingSphere {
+    pub fn center_at(&self, time: f32) -> Vec3 {
+        let next_idx = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+
+        if next_idx == 0 {
+            return self.keyframes[0].center;
+        }
+        if next_idx == self.keyframes.len() {
+            return self.keyframes[next_idx - 1].center;
+        }
+
+        let prev = &self.keyframes[next_idx - 1];
+        let next = &self.keyframes[next_idx];
+        let progress = (time - prev.time) / (next.time - prev.time);
+
+        prev.center.lerp(next.center, progress)
+    }
+
+    /// Hits the sphere where it is at the time of the ray.
+    pub fn hit(&self, ray: &Ray3, tmin: f32, tmax: f32) -> Option<HitInfo> {
+        self.sphere_at(ray.time).hit(ray, tmin, tmax)
+    }
+
+    /// Encloses the whole path of the sphere.
+    pub fn aabb(&self) -> Aabb {
+        self.keyframes.iter().fold(Aabb::EMPTY, |aabb, keyframe| {
+            aabb.union(&self.sphere_at(keyframe.time).aabb())
+        })
+    }
+
+    fn sphere_at(&self, time: f32) -> Sphere {
+        Sphere {
+            center: self.center_at(time),
+            radius: self.radius,
+        }
+    }
+}
+
+impl TryFrom<MovingSphereSource> for MovingSphere {
+    type Error = String;
+
+    fn try_from(source: MovingSphereSource) -> Result<Self, Self::Error> {
+        if source.keyframes.is_empty() {
+            return Err("Moving sphere has no keyframes".to_owned());
+        }
+
+        let is_sorted = source
+            .keyframes
+            .windows(2)
+            .all(|pair| pair[0].time <= pair[1].time);
+        if !is_sorted {
+            return Err("Moving sphere's keyframes aren't sorted by time".to_owned());
+        }
+
+        Ok(MovingSphere {
+            keyframes: source.keyframes,
+            radius: source.radius,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moving_sphere() -> MovingSphere {
+        MovingSphere {
+            keyframes: vec![
+                Keyframe {
+                    time: 0.0,
+                    center: Vec3::ZERO,
+                },
+                Keyframe {
+                    time: 1.0,
+                    center: Vec3::new(4.0, 0.0, 0.0),
+                },
+                Keyframe {
+                    time: 2.0,
+                    center: Vec3::new(4.0, 2.0, 0.0),
+                },
+            ],
+            radius: 0.5,
+        }
+    }
+
+    #[test]
+    fn center_is_interpolated_between_keyframes() {
+        let sphere = moving_sphere();
+
+        assert_eq!(Vec3::ZERO, sphere.center_at(-1.0));
+        assert_eq!(Vec3::new(1.0, 0.0, 0.0), sphere.center_at(0.25));
+        assert_eq!(Vec3::new(4.0, 0.0, 0.0), sphere.center_at(1.0));
+        assert_eq!(Vec3::new(4.0, 1.0, 0.0), sphere.center_at(1.5));
+        assert_eq!(Vec3::new(4.0, 2.0, 0.0), sphere.center_at(3.0));
+    }
+
+    #[test]
+    fn ray_hits_sphere_only_when_it_is_there() {
+        let sphere = moving_sphere();
+        let ray_at = |time| Ray3::at_time(Vec3::new(4.0, 0.0, 5.0), -Vec3::Z, time);
+
+        assert!(sphere.hit(&ray_at(0.0), 0.0, f32::MAX).is_none());
+        assert_eq!(4.5, sphere.hit(&ray_at(1.0), 0.0, f32::MAX).unwrap().t);
+        assert!(sphere.hit(&ray_at(2.0), 0.0, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn aabb_encloses_the_whole_path() {
+        let aabb = moving_sphere().aabb();
+
+        assert_eq!(Vec3::new(-0.5, -0.5, -0.5), aabb.min);
+        assert_eq!(Vec3::new(4.5, 2.5, 0.5), aabb.max);
+    }
+
+    #[test]
+    fn moving_sphere_without_keyframes_is_rejected() {
+        let err = serde_json::from_str::<MovingSphere>(r#"{"keyframes": [], "radius": 1}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("no keyframes"), "{}", err);
+    }
+
+    #[test]
+    fn moving_sphere_with_unsorted_keyframes_is_rejected() {
+        let json = r#"{
+            "keyframes": [
+                {"time": 1, "center": [0, 0, 0]},
+                {"time": 0, "center": [1, 0, 0]}
+            ],
+            "radius": 1
+        }"#;
+        let err = serde_json::from_str::<MovingSphere>(json).err().unwrap();
+        assert!(err.to_string().contains("aren't sorted"), "{}", err);
+    }
+}
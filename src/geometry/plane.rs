@@ -1,11 +1,18 @@
 use glam::Vec3;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::bvh::Aabb;
 use crate::primitive::Ray3;
 
-use super::HitInfo;
+use super::{HitInfo, SurfaceSample, Tbn3};
 
+/// A finite rectangle centered at `center`. `u` and `v` go from the center to
+/// the middle of the rectangle's edges. They are expected to be perpendicular.
+///
+/// The normal of the plane is `u` cross `v`. Texture coordinates go from 0 to
+/// 1 along `u` and from 0 to 1 against `v`. So an image texture appears upright
+/// when `v` points up.
 #[derive(Serialize, Deserialize)]
 pub struct Plane {
     pub center: Vec3,
@@ -41,10 +48,120 @@ impl Plane {
             return None;
         }
 
-        todo!("Add UV calculation")
+        let u = (1.0 + self.u.dot(sep) / self.u.length_squared()) / 2.0;
+        let v = (1.0 - self.v.dot(sep) / self.v.length_squared()) / 2.0;
+
+        Some(HitInfo {
+            t,
+            u,
+            v,
+            pos,
+            tbn: Tbn3::from_tn(self.u, normal),
+            // UVs go from 0 to 1 across the whole plane.
+            uv_density: 1.0 / self.area(),
+        })
     }
 
     pub fn aabb(&self) -> Aabb {
-        todo!("Add AABB computation logic")
+        // A plane aligned with an axis has no thickness along it. Padding the
+        // box keeps rays from slipping past it due to rounding errors.
+        const PADDING: f32 = 0.0001;
+
+        let half_extent = (self.u.abs() + self.v.abs()).max(Vec3::splat(PADDING));
+        Aabb {
+            min: self.center - half_extent,
+            max: self.center + half_extent,
+        }
+    }
+
+    pub fn area(&self) -> f32 {
+        4.0 * self.u.cross(self.v).length()
+    }
+
+    /// Picks a point uniformly on the plane.
+    pub fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        let a = 2.0 * rng.gen::<f32>() - 1.0;
+        let b = 2.0 * rng.gen::<f32>() - 1.0;
+
+        SurfaceSample {
+            pos: self.center + a * self.u + b * self.v,
+            normal: self.u.cross(self.v).normalize(),
+            pdf: 1.0 / self.area(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use crate::{geometry::Tbn3, primitive::Ray3};
+
+    use super::*;
+
+    fn xy_plane() -> Plane {
+        Plane {
+            center: Vec3::ZERO,
+            u: 2.0 * Vec3::X,
+            v: Vec3::Y,
+        }
+    }
+
+    #[test]
+    fn plane_hit_values_are_correct_at_the_center() {
+        let plane = xy_plane();
+
+        let ray = Ray3::new(2.0 * Vec3::Z, -Vec3::Z);
+        let hit = plane.hit(&ray, 0.0, 4.0).unwrap();
+
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.pos, Vec3::ZERO);
+        assert_eq!((hit.u, hit.v), (0.5, 0.5));
+        assert_eq!(hit.tbn, Tbn3::from_tn(Vec3::X, Vec3::Z));
+    }
+
+    #[test]
+    fn plane_hit_uvs_span_the_whole_rectangle() {
+        let plane = xy_plane();
+
+        // top left corner
+        let ray = Ray3::new(Vec3::new(-2.0, 1.0, 2.0), -Vec3::Z);
+        let hit = plane.hit(&ray, 0.0, 4.0).unwrap();
+        assert_eq!((hit.u, hit.v), (0.0, 0.0));
+
+        // bottom right corner
+        let ray = Ray3::new(Vec3::new(2.0, -1.0, 2.0), -Vec3::Z);
+        let hit = plane.hit(&ray, 0.0, 4.0).unwrap();
+        assert_eq!((hit.u, hit.v), (1.0, 1.0));
+
+        // middle of the right edge, hit from behind
+        let ray = Ray3::new(Vec3::new(2.0, 0.0, -2.0), Vec3::Z);
+        let hit = plane.hit(&ray, 0.0, 4.0).unwrap();
+        assert_eq!((hit.u, hit.v), (1.0, 0.5));
+        assert_eq!(hit.tbn.n, Vec3::Z);
+    }
+
+    #[test]
+    fn plane_hit_rejects_rays_outside_the_rectangle() {
+        let plane = xy_plane();
+
+        let ray = Ray3::new(Vec3::new(2.5, 0.0, 2.0), -Vec3::Z);
+        assert!(plane.hit(&ray, 0.0, 4.0).is_none());
+
+        let ray = Ray3::new(Vec3::new(0.0, -1.5, 2.0), -Vec3::Z);
+        assert!(plane.hit(&ray, 0.0, 4.0).is_none());
+    }
+
+    #[test]
+    fn plane_aabb_encloses_all_corners() {
+        let plane = Plane {
+            center: Vec3::new(1.0, 2.0, 3.0),
+            u: Vec3::new(1.0, 0.0, 1.0),
+            v: Vec3::new(1.0, 0.0, -1.0),
+        };
+        let aabb = plane.aabb();
+
+        assert_eq!(aabb.min, Vec3::new(-1.0, 2.0 - 0.0001, 1.0));
+        assert_eq!(aabb.max, Vec3::new(3.0, 2.0 + 0.0001, 5.0));
     }
 }
@@ -1,11 +1,14 @@
+use std::f32::consts::{PI, TAU};
+
 use glam::Vec3;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::bvh::Aabb;
 use crate::primitive::Ray3;
 use crate::util::compute_uv_on_sphere_from_normal;
 
-use super::{HitInfo, Tbn3};
+use super::{HitInfo, SurfaceSample, Tbn3};
 
 #[derive(Serialize, Deserialize)]
 pub struct Sphere {
@@ -40,6 +43,7 @@ impl Sphere {
                         v,
                         pos,
                         tbn: Tbn3::from_tn(Vec3::Y.cross(normal), normal),
+                        uv_density: self.uv_density(normal),
                     });
                 }
             }
@@ -55,6 +59,32 @@ impl Sphere {
             max: self.center + radius,
         }
     }
+
+    /// UVs are spread over the sphere like latitudes and longitudes, so they
+    /// get denser towards the poles.
+    fn uv_density(&self, normal: Vec3) -> f32 {
+        let sin_polar_angle = normal.x.hypot(normal.z).max(f32::EPSILON);
+        1.0 / (2.0 * PI * PI * self.radius.powi(2) * sin_polar_angle)
+    }
+
+    pub fn area(&self) -> f32 {
+        4.0 * PI * self.radius.powi(2)
+    }
+
+    /// Picks a point uniformly on the sphere.
+    pub fn sample_surface<R: Rng>(&self, rng: &mut R) -> SurfaceSample {
+        // Archimedes' hat-box theorem: z is uniform for uniform points.
+        let z = 1.0 - 2.0 * rng.gen::<f32>();
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = TAU * rng.gen::<f32>();
+        let normal = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+
+        SurfaceSample {
+            pos: self.center + self.radius * normal,
+            normal,
+            pdf: 1.0 / self.area(),
+        }
+    }
 }
 
 #[cfg(test)]
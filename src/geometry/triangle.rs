@@ -0,0 +1,196 @@
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::bvh::Aabb;
+use crate::primitive::Ray3;
+
+use super::{HitInfo, Tbn3};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Vertex {
+    pub pos: Vec3,
+    pub normal: Vec3,
+    pub uv: Vec2,
+    /// Points in the direction in which u increases. It needn't be normalized
+    /// or perpendicular to the normal. That's fixed while hitting.
+    pub tangent: Vec3,
+}
+
+/// A triangle whose vertices carry their own normals, UVs and tangents. These
+/// are interpolated across the face, so meshes made of triangles look smooth.
+///
+/// The front face is the one from which the vertices appear anti clockwise.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Triangle {
+    pub vertices: [Vertex; 3],
+}
+
+impl Triangle {
+    /// Creates a flat triangle. Its UVs are (0, 1), (1, 1) and (0, 0) at `a`,
+    /// `b` and `c` respectively.
+    pub fn flat(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        let normal = (b - a).cross(c - a).normalize();
+        let tangent = b - a;
+        let vertex = |pos, uv| Vertex {
+            pos,
+            normal,
+            uv,
+            tangent,
+        };
+
+        Triangle {
+            vertices: [
+                vertex(a, Vec2::new(0.0, 1.0)),
+                vertex(b, Vec2::new(1.0, 1.0)),
+                vertex(c, Vec2::new(0.0, 0.0)),
+            ],
+        }
+    }
+
+    /// Uses the Möller-Trumbore algorithm.
+    pub fn hit(&self, ray: &Ray3, tmin: f32, tmax: f32) -> Option<HitInfo> {
+        let [a, b, c] = &self.vertices;
+
+        let e1 = b.pos - a.pos;
+        let e2 = c.pos - a.pos;
+
+        let p = ray.dir.cross(e2);
+        let det = e1.dot(p);
+
+        if det.abs() < f32::EPSILON {
+            // Ray is parallel to the triangle
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = ray.pos - a.pos;
+
+        // Barycentric weights of b and c
+        let wb = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&wb) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let wc = ray.dir.dot(q) * inv_det;
+        if wc < 0.0 || wb + wc > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+
+        if t < tmin || t > tmax {
+            return None;
+        }
+
+        let wa = 1.0 - wb - wc;
+
+        let normal = (wa * a.normal + wb * b.normal + wc * c.normal).normalize();
+        let uv = wa * a.uv + wb * b.uv + wc * c.uv;
+        let tangent = wa * a.tangent + wb * b.tangent + wc * c.tangent;
+
+        Some(HitInfo {
+            t,
+            u: uv.x,
+            v: uv.y,
+            pos: ray.point_at_param(t),
+            tbn: Tbn3::from_tn(orthogonalize(tangent, normal), normal),
+            uv_density: (b.uv - a.uv).perp_dot(c.uv - a.uv).abs() / e1.cross(e2).length(),
+        })
+    }
+
+    pub fn aabb(&self) -> Aabb {
+        let [a, b, c] = &self.vertices;
+        Aabb::EMPTY.grow(a.pos).grow(b.pos).grow(c.pos)
+    }
+}
+
+/// Makes `tangent` perpendicular to `normal`. If that's not possible, any
+/// vector perpendicular to `normal` is returned.
+fn orthogonalize(tangent: Vec3, normal: Vec3) -> Vec3 {
+    let tangent = tangent - normal * normal.dot(tangent);
+
+    if tangent.length_squared() > f32::EPSILON {
+        tangent
+    } else if normal.y.abs() < 0.9 {
+        Vec3::Y.cross(normal)
+    } else {
+        Vec3::X.cross(normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Vec2, Vec3};
+
+    use crate::{geometry::Tbn3, primitive::Ray3};
+
+    use super::*;
+
+    fn xy_triangle() -> Triangle {
+        Triangle::flat(Vec3::ZERO, Vec3::X, Vec3::Y)
+    }
+
+    #[test]
+    fn triangle_hit_values_are_correct_at_the_corners() {
+        let triangle = xy_triangle();
+
+        let ray = Ray3::new(Vec3::Z, -Vec3::Z);
+        let hit = triangle.hit(&ray, 0.0, 2.0).unwrap();
+
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.pos, Vec3::ZERO);
+        assert_eq!((hit.u, hit.v), (0.0, 1.0));
+        assert_eq!(hit.tbn, Tbn3::from_tn(Vec3::X, Vec3::Z));
+
+        let ray = Ray3::new(Vec3::new(0.0, 1.0, 1.0), -Vec3::Z);
+        let hit = triangle.hit(&ray, 0.0, 2.0).unwrap();
+
+        assert_eq!((hit.u, hit.v), (0.0, 0.0));
+    }
+
+    #[test]
+    fn triangle_hit_interpolates_vertex_normals() {
+        let mut triangle = xy_triangle();
+        triangle.vertices[1].normal = Vec3::X;
+        triangle.vertices[2].normal = Vec3::X;
+
+        let ray = Ray3::new(Vec3::new(0.25, 0.25, 1.0), -Vec3::Z);
+        let hit = triangle.hit(&ray, 0.0, 2.0).unwrap();
+
+        assert!(hit
+            .tbn
+            .n
+            .abs_diff_eq(Vec3::new(1.0, 0.0, 1.0).normalize(), 1e-6));
+        assert!(hit.tbn.t.dot(hit.tbn.n).abs() < 1e-6);
+        assert_eq!(Vec2::new(hit.u, hit.v), Vec2::new(0.25, 0.75));
+    }
+
+    #[test]
+    fn triangle_hit_rejects_rays_outside_the_triangle() {
+        let triangle = xy_triangle();
+
+        let ray = Ray3::new(Vec3::new(0.6, 0.6, 1.0), -Vec3::Z);
+        assert!(triangle.hit(&ray, 0.0, 2.0).is_none());
+
+        let ray = Ray3::new(Vec3::new(-0.1, 0.5, 1.0), -Vec3::Z);
+        assert!(triangle.hit(&ray, 0.0, 2.0).is_none());
+
+        // parallel to the triangle
+        let ray = Ray3::new(Vec3::new(-1.0, 0.2, 0.0), Vec3::X);
+        assert!(triangle.hit(&ray, 0.0, 2.0).is_none());
+    }
+
+    #[test]
+    fn triangle_aabb_encloses_all_vertices() {
+        let triangle = Triangle::flat(
+            Vec3::new(1.0, -2.0, 0.0),
+            Vec3::new(0.0, 3.0, 1.0),
+            Vec3::new(-1.0, 0.0, 2.0),
+        );
+        let aabb = triangle.aabb();
+
+        assert_eq!(aabb.min, Vec3::new(-1.0, -2.0, 0.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 3.0, 2.0));
+    }
+}
@@ -0,0 +1,422 @@
+use std::convert::TryInto;
+use std::path::Path;
+
+use ::gltf::image::{Data, Format, Source};
+use ::gltf::mesh::Mode;
+use ::gltf::{buffer, Node, Primitive};
+use glam::{Mat4, Vec2, Vec3};
+use image::{DynamicImage, ImageBuffer};
+
+use crate::bvh::Aabb;
+use crate::cache::Cache;
+use crate::camera::{CameraInitOptions, Projection};
+use crate::error::{Error, Result};
+use crate::geometry::{Geometry, IndexedTriangles, Mesh};
+use crate::item::Item;
+use crate::material::{Absorption, Dielectric, Light, Material, Principled};
+use crate::scene::{Scene, SceneDescription};
+use crate::sky_box::{Cubemap, SkyBox};
+use crate::texture::{Image, MipMap, Solid, Texture};
+use crate::transform::{Rotation, TransformInitOptions};
+
+/// glTF cameras needn't specify an aspect ratio. This matches the default
+/// canvas size of the ray tracer.
+const DEFAULT_ASPECT: f32 = 2.0;
+
+/// Vertical field of view of the camera made up for files without one.
+const DEFAULT_VT_FOV: f32 = 40.0;
+
+/// Creates a scene out of a glTF 2.0 file (either .gltf or .glb).
+///
+/// The scene is lit by a plain white sky box since glTF has no concept of one.
+/// If the file has no camera, a camera looking at the whole scene
+/// from the front is made up.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Scene> {
+    let mut texture_cache = Cache::new();
+    let sky_idx = texture_cache.add("sky", Texture::Solid(Solid { color: Vec3::ONE }));
+
+    let mut scene = SceneDescription {
+        sky_box: SkyBox::Cubemap(Cubemap::all(sky_idx)),
+        camera: camera_looking_at(&Aabb::EMPTY).into(),
+        texture_cache,
+        geometry_cache: Cache::new(),
+        material_cache: Cache::new(),
+        items: vec![],
+        models: vec![],
+    };
+
+    let camera = match import(path, &mut scene)? {
+        Some(camera) => camera,
+        None => {
+            let aabb = scene.items.iter().fold(Aabb::EMPTY, |aabb, item| {
+                aabb.union(&item.aabb(&scene.geometry_cache))
+            });
+            camera_looking_at(&aabb)
+        }
+    };
+
+    scene.camera = camera.into();
+    scene.try_into()
+}
+
+/// Adds the meshes in the default scene of a glTF 2.0 file to the scene. Every
+/// node becomes an item whose transform is the node's. Nodes which share a
+/// mesh share its geometry too. Returns the first camera in the
+/// file, if any.
+///
+/// PBR metallic-roughness materials are mapped as follows:
+/// * emissive ones become lights,
+/// * transmissive or alpha blended ones become dielectrics,
+/// * everything else becomes principled. Its metallic and roughness factors
+///   are scaled by the blue and green channels of the metallic-roughness
+///   texture, like in glTF.
+///
+/// Base color textures are multiplied by the base color factor.
+pub fn import<P: AsRef<Path>>(
+    path: P,
+    scene: &mut SceneDescription,
+) -> Result<Option<CameraInitOptions>> {
+    let path = path.as_ref();
+    let (document, buffers, images) =
+        ::gltf::import(path).map_err(|err| Error::invalid_model(path, err))?;
+
+    let mut importer = Importer {
+        path,
+        name: path
+            .to_str()
+            .ok_or_else(|| Error::invalid_model(path, "Non UTF-8 path"))?,
+        buffers: &buffers,
+        images: &images,
+        scene,
+        camera: None,
+    };
+
+    if let Some(gltf_scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in gltf_scene.nodes() {
+            importer.import_node(&node, Mat4::IDENTITY)?;
+        }
+    }
+
+    Ok(importer.camera)
+}
+
+struct Importer<'a> {
+    path: &'a Path,
+    /// Prefix for the names of the cache entries made by the importer.
+    name: &'a str,
+    buffers: &'a [buffer::Data],
+    images: &'a [Data],
+    scene: &'a mut SceneDescription,
+    camera: Option<CameraInitOptions>,
+}
+
+impl<'a> Importer<'a> {
+    fn import_node(&mut self, node: &Node, parent_transform: Mat4) -> Result<()> {
+        let transform = parent_transform * Mat4::from_cols_array_2d(&node.transform().matrix());
+
+        if let (None, Some(camera)) = (&self.camera, node.camera()) {
+            let (vt_fov, aspect, projection) = match camera.projection() {
+                gltf::camera::Projection::Perspective(perspective) => (
+                    perspective.yfov().to_degrees(),
+                    perspective.aspect_ratio().unwrap_or(DEFAULT_ASPECT),
+                    Projection::Perspective,
+                ),
+                // xmag and ymag are half the width and height of the view.
+                gltf::camera::Projection::Orthographic(orthographic) => (
+                    DEFAULT_VT_FOV,
+                    orthographic.xmag() / orthographic.ymag(),
+                    Projection::Orthographic {
+                        width: 2.0 * orthographic.xmag(),
+                    },
+                ),
+            };
+
+            self.camera = Some(CameraInitOptions {
+                pos: transform.transform_point3(Vec3::ZERO),
+                look_at: transform.transform_point3(-Vec3::Z),
+                vup: transform.transform_vector3(Vec3::Y),
+                vt_fov,
+                aspect,
+                projection,
+                aperture: 0.0,
+                focus_distance: None,
+                shutter_open: 0.0,
+                shutter_close: 0.0,
+            });
+        }
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.import_primitive(&mesh, &primitive, transform)?;
+            }
+        }
+
+        for child in node.children() {
+            self.import_node(&child, transform)?;
+        }
+
+        Ok(())
+    }
+
+    fn import_primitive(
+        &mut self,
+        mesh: &::gltf::Mesh,
+        primitive: &Primitive,
+        transform: Mat4,
+    ) -> Result<()> {
+        if primitive.mode() != Mode::Triangles {
+            return Ok(());
+        }
+
+        let name = format!(
+            "{}:mesh{}:primitive{}",
+            self.name,
+            mesh.index(),
+            primitive.index()
+        );
+
+        let geometry_idx = match self.scene.geometry_cache.idx_for(&name) {
+            Some(idx) => idx,
+            None => {
+                let triangles = self.read_triangles(primitive).triangles();
+                self.scene
+                    .geometry_cache
+                    .add(&name, Geometry::Mesh(Mesh::new(triangles)))
+            }
+        };
+
+        let material_idx = self.material(primitive.material())?;
+
+        // Shears can't be represented by our transforms. They're rare enough
+        // to be ignored.
+        let (scale, rotation, translation) = transform.to_scale_rotation_translation();
+
+        let transform = if transform == Mat4::IDENTITY {
+            None
+        } else {
+            let options = TransformInitOptions {
+                translation,
+                rotation: Rotation::Quat(rotation),
+                scale,
+            };
+            match options.try_into() {
+                Ok(transform) => Some(transform),
+                // Nodes scaled down to nothing are hidden.
+                Err(_) => return Ok(()),
+            }
+        };
+
+        self.scene.items.push(Item {
+            geometry_idx,
+            material_idx,
+            transform,
+        });
+
+        Ok(())
+    }
+
+    fn read_triangles(&self, primitive: &Primitive) -> IndexedTriangles {
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+        let positions: Vec<Vec3> = reader
+            .read_positions()
+            .map(|positions| positions.map(Vec3::from).collect())
+            .unwrap_or_default();
+
+        IndexedTriangles {
+            normals: reader
+                .read_normals()
+                .map(|normals| normals.map(Vec3::from).collect())
+                .unwrap_or_default(),
+            // glTF's UVs already start at the top left corner like ours do.
+            uvs: reader
+                .read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().map(Vec2::from).collect())
+                .unwrap_or_default(),
+            tangents: reader
+                .read_tangents()
+                .map(|tangents| tangents.map(|[x, y, z, _]| Vec3::new(x, y, z)).collect())
+                .unwrap_or_default(),
+            indices: reader
+                .read_indices()
+                .map(|indices| indices.into_u32().collect())
+                .unwrap_or_else(|| (0..positions.len() as u32).collect()),
+            positions,
+        }
+    }
+
+    fn material(&mut self, material: ::gltf::Material) -> Result<usize> {
+        let name = match material.index() {
+            Some(idx) => format!("{}:material{}", self.name, idx),
+            None => format!("{}:default_material", self.name),
+        };
+
+        if let Some(idx) = self.scene.material_cache.idx_for(&name) {
+            return Ok(idx);
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let normal_map_idx = match material.normal_texture() {
+            Some(info) => Some(self.texture(&info.texture())?),
+            None => None,
+        };
+        let emissive_factor = Vec3::from(material.emissive_factor());
+
+        let material = if emissive_factor != Vec3::ZERO {
+            Material::Light(Light {
+                texture_idx: match material.emissive_texture() {
+                    Some(info) => self.texture(&info.texture())?,
+                    None => self.solid_texture(&name, Vec3::ONE),
+                },
+                brightness: emissive_factor,
+                one_sided: false,
+            })
+        } else if material.transmission().is_some()
+            || material.alpha_mode() == ::gltf::material::AlphaMode::Blend
+        {
+            Material::Dielectric(Dielectric {
+                ref_idx: material.ior().unwrap_or(1.5),
+                normal_map_idx,
+                roughness: pbr.roughness_factor(),
+                // White light turns into the attenuation color after the
+                // attenuation distance, which is infinite by default.
+                absorption: material
+                    .volume()
+                    .filter(|volume| volume.attenuation_distance().is_finite())
+                    .map(|volume| Absorption {
+                        color: Vec3::from(volume.attenuation_color()),
+                        density: 1.0 / volume.attenuation_distance(),
+                    }),
+                dispersion: None,
+                emission: None,
+            })
+        } else {
+            let [r, g, b, _] = pbr.base_color_factor();
+            let base_color_factor = Vec3::new(r, g, b);
+            let texture_idx = match pbr.base_color_texture() {
+                Some(info) => {
+                    let texture_idx = self.texture(&info.texture())?;
+                    self.tinted_texture(&name, texture_idx, base_color_factor)
+                }
+                None => self.solid_texture(&name, base_color_factor),
+            };
+
+            let metallic_roughness_idx = match pbr.metallic_roughness_texture() {
+                Some(info) => Some(self.texture(&info.texture())?),
+                None => None,
+            };
+
+            Material::Principled(Principled {
+                base_color_idx: texture_idx,
+                normal_map_idx,
+                metallic: pbr.metallic_factor(),
+                metallic_idx: metallic_roughness_idx,
+                roughness: pbr.roughness_factor(),
+                roughness_idx: metallic_roughness_idx,
+                emission: None,
+            })
+        };
+
+        Ok(self.scene.material_cache.add(&name, material))
+    }
+
+    /// glTF multiplies textures by their factors. Textures are shared, so a
+    /// factor other than 1 gets its own copy of the texture, which shares
+    /// the decoded image.
+    fn tinted_texture(&mut self, name: &str, texture_idx: usize, tint: Vec3) -> usize {
+        let texture_cache = &mut self.scene.texture_cache;
+        let tinted = match &texture_cache[texture_idx] {
+            Texture::Image(image) if tint != Vec3::ONE => Texture::Image(image.tinted(tint)),
+            _ => return texture_idx,
+        };
+
+        texture_cache
+            .idx_for(name)
+            .unwrap_or_else(|| texture_cache.add(name, tinted))
+    }
+
+    fn solid_texture(&mut self, name: &str, color: Vec3) -> usize {
+        let texture_cache = &mut self.scene.texture_cache;
+        texture_cache
+            .idx_for(name)
+            .unwrap_or_else(|| texture_cache.add(name, Texture::Solid(Solid { color })))
+    }
+
+    /// Images stored in separate files are named after their path. Embedded
+    /// ones are named after their index in the glTF file.
+    fn texture(&mut self, texture: &::gltf::Texture) -> Result<usize> {
+        let image = texture.source();
+        let (name, embedded) = match image.source() {
+            Source::Uri { uri, .. } if !uri.starts_with("data:") => {
+                let path = self
+                    .path
+                    .parent()
+                    .unwrap_or_else(|| Path::new(""))
+                    .join(uri);
+                let name = path
+                    .to_str()
+                    .ok_or_else(|| Error::invalid_model(&path, "Non UTF-8 path"))?
+                    .to_owned();
+                (name, false)
+            }
+            _ => (format!("{}#image{}", self.name, image.index()), true),
+        };
+
+        if let Some(idx) = self.scene.texture_cache.idx_for(&name) {
+            return Ok(idx);
+        }
+
+        let data = &self.images[image.index()];
+        let image = dynamic_image(data).ok_or_else(|| Error::UnsupportedImageFormat {
+            path: name.clone().into(),
+            message: format!("{:?} pixels aren't supported", data.format),
+        })?;
+
+        let image = if embedded {
+            Image::embedded(name.clone(), MipMap::new(image))
+        } else {
+            Image::new(name.clone(), MipMap::new(image))
+        };
+
+        Ok(self.scene.texture_cache.add(&name, Texture::Image(image)))
+    }
+}
+
+fn dynamic_image(data: &Data) -> Option<DynamicImage> {
+    let (wd, ht, pixels) = (data.width, data.height, data.pixels.clone());
+
+    match data.format {
+        Format::R8 => ImageBuffer::from_raw(wd, ht, pixels).map(DynamicImage::ImageLuma8),
+        Format::R8G8 => ImageBuffer::from_raw(wd, ht, pixels).map(DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => ImageBuffer::from_raw(wd, ht, pixels).map(DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => ImageBuffer::from_raw(wd, ht, pixels).map(DynamicImage::ImageRgba8),
+        _ => None,
+    }
+}
+
+/// Makes up a camera which looks at the front of the box and sees all of it.
+fn camera_looking_at(aabb: &Aabb) -> CameraInitOptions {
+    let (center, radius) = if aabb.min.cmple(aabb.max).all() {
+        (aabb.centroid(), (aabb.max - aabb.min).length() / 2.0)
+    } else {
+        (Vec3::ZERO, 1.0)
+    };
+    let distance = radius / (DEFAULT_VT_FOV.to_radians() / 2.0).sin();
+
+    CameraInitOptions {
+        pos: center + distance * Vec3::Z,
+        look_at: center,
+        vup: Vec3::Y,
+        vt_fov: DEFAULT_VT_FOV,
+        aspect: DEFAULT_ASPECT,
+        projection: Projection::Perspective,
+        aperture: 0.0,
+        focus_distance: None,
+        shutter_open: 0.0,
+        shutter_close: 0.0,
+    }
+}
@@ -0,0 +1,35 @@
+//! Importers which add assets made in other tools to a scene.
+
+pub mod gltf;
+pub mod obj;
+
+use serde::{Deserialize, Serialize};
+
+use crate::assets::AssetPaths;
+use crate::error::{Error, Result};
+use crate::scene::SceneDescription;
+
+/// An asset file referenced from a scene. Its contents are added to the scene
+/// as items when the scene is loaded. The extension of the file decides how
+/// it's read. Cameras in glTF files are ignored.
+#[derive(Serialize, Deserialize)]
+pub struct Model {
+    pub path: String,
+}
+
+impl Model {
+    /// Relative paths are looked up as `assets` describes.
+    pub fn import(&self, scene: &mut SceneDescription, assets: &AssetPaths) -> Result<()> {
+        let path = assets.resolve(&self.path);
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+
+        match extension.as_deref() {
+            Some("gltf") | Some("glb") => self::gltf::import(&path, scene).map(|_| ()),
+            Some("obj") => obj::import(&path, scene),
+            _ => Err(Error::invalid_model(path, "unsupported model format")),
+        }
+    }
+}
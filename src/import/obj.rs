@@ -0,0 +1,268 @@
+use std::path::Path;
+
+use crate::cache::Cache;
+use crate::error::{Error, Result};
+use crate::geometry::{load_obj, obj_material_name, Geometry, Mesh};
+use crate::item::Item;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::scene::SceneDescription;
+use crate::texture::{Image, Solid, Texture};
+
+/// Adds the contents of a Wavefront OBJ file to the scene. The faces are
+/// grouped by their MTL material and every group becomes an item with its own
+/// mesh and material.
+///
+/// MTL materials are mapped as follows:
+/// * transparent ones (`d` < 1 or `illum` 4, 6, 7 or 9) become dielectrics,
+/// * reflective ones (`illum` 3, 5 or 8) become metals,
+/// * everything else becomes lambertian.
+///
+/// Faces get a gray lambertian material when the MTL file is missing.
+///
+/// Cache entries are named after the OBJ file, so importing the same file
+/// twice reuses its meshes and materials.
+pub fn import<P: AsRef<Path>>(path: P, scene: &mut SceneDescription) -> Result<()> {
+    let path = path.as_ref();
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| Error::invalid_model(path, "Non UTF-8 path"))?;
+
+    // Without the MTL file every face gets the default material.
+    let (models, materials) = load_obj(path)?;
+
+    let mut groups: Vec<(&str, Vec<&tobj::Model>)> = vec![];
+    for model in &models {
+        let mtl_name = obj_material_name(model, &materials);
+        match groups.iter_mut().find(|(name, _)| *name == mtl_name) {
+            Some((_, group)) => group.push(model),
+            None => groups.push((mtl_name, vec![model])),
+        }
+    }
+
+    for (mtl_name, group) in groups {
+        let mtl = materials.iter().find(|mtl| mtl.name == mtl_name);
+        let name = format!("{}:{}", path_str, mtl_name);
+
+        let geometry_idx = match scene.geometry_cache.idx_for(&name) {
+            Some(idx) => idx,
+            None => {
+                let mesh = Mesh::from_obj_models(path, Some(mtl_name), group)?;
+                scene.geometry_cache.add(&name, Geometry::Mesh(mesh))
+            }
+        };
+
+        let material_idx = match scene.material_cache.idx_for(&name) {
+            Some(idx) => idx,
+            None => {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                let material = material(mtl, &name, dir, &mut scene.texture_cache)?;
+                scene.material_cache.add(&name, material)
+            }
+        };
+
+        scene.items.push(Item {
+            geometry_idx,
+            material_idx,
+            transform: None,
+        });
+    }
+
+    Ok(())
+}
+
+/// Converts an MTL material to one of ours. Faces without a material get a
+/// gray lambertian one. Texture paths are relative to `dir`.
+fn material(
+    mtl: Option<&tobj::Material>,
+    name: &str,
+    dir: &Path,
+    texture_cache: &mut Cache<Texture>,
+) -> Result<Material> {
+    let mtl = match mtl {
+        Some(mtl) => mtl,
+        None => {
+            return Ok(Material::Lambertian(Lambertian {
+                texture_idx: solid_texture(texture_cache, name, [0.5; 3]),
+                normal_map_idx: None,
+                emission: None,
+            }))
+        }
+    };
+
+    let normal_map_idx = if mtl.normal_texture.is_empty() {
+        None
+    } else {
+        Some(image_texture(
+            texture_cache,
+            &dir.join(&mtl.normal_texture),
+        )?)
+    };
+
+    if mtl.dissolve < 1.0 || matches!(mtl.illumination_model, Some(4 | 6 | 7 | 9)) {
+        Ok(Material::Dielectric(Dielectric {
+            ref_idx: if mtl.optical_density > 1.0 {
+                mtl.optical_density
+            } else {
+                1.5
+            },
+            normal_map_idx,
+            roughness: 0.0,
+            absorption: None,
+            dispersion: None,
+            emission: None,
+        }))
+    } else if matches!(mtl.illumination_model, Some(3 | 5 | 8)) {
+        let texture_idx = if !mtl.specular_texture.is_empty() {
+            image_texture(texture_cache, &dir.join(&mtl.specular_texture))?
+        } else if mtl.specular != [0.0; 3] {
+            solid_texture(texture_cache, name, mtl.specular)
+        } else {
+            solid_texture(texture_cache, name, mtl.diffuse)
+        };
+
+        Ok(Material::Metal(Metal {
+            texture_idx,
+            normal_map_idx,
+            // Maps the Phong exponent to a roughness in [0, 1].
+            fuzz: (2.0 / (mtl.shininess.max(0.0) + 2.0)).sqrt(),
+            emission: None,
+        }))
+    } else {
+        let texture_idx = if !mtl.diffuse_texture.is_empty() {
+            image_texture(texture_cache, &dir.join(&mtl.diffuse_texture))?
+        } else {
+            solid_texture(texture_cache, name, mtl.diffuse)
+        };
+
+        Ok(Material::Lambertian(Lambertian {
+            texture_idx,
+            normal_map_idx,
+            emission: None,
+        }))
+    }
+}
+
+fn solid_texture(texture_cache: &mut Cache<Texture>, name: &str, color: [f32; 3]) -> usize {
+    texture_cache.idx_for(name).unwrap_or_else(|| {
+        texture_cache.add(
+            name,
+            Texture::Solid(Solid {
+                color: color.into(),
+            }),
+        )
+    })
+}
+
+/// Image textures are named after their path so that materials sharing an
+/// image share the texture too.
+fn image_texture(texture_cache: &mut Cache<Texture>, path: &Path) -> Result<usize> {
+    let name = path
+        .to_str()
+        .ok_or_else(|| Error::invalid_model(path, "Non UTF-8 path"))?;
+
+    match texture_cache.idx_for(name) {
+        Some(idx) => Ok(idx),
+        None => {
+            let image = Image::load(path)?;
+            Ok(texture_cache.add(name, Texture::Image(image)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(mtl: &tobj::Material) -> Material {
+        material(Some(mtl), "test", Path::new(""), &mut Cache::new()).unwrap()
+    }
+
+    #[test]
+    fn plain_mtl_becomes_lambertian() {
+        let mtl = tobj::Material {
+            diffuse: [0.8, 0.1, 0.1],
+            illumination_model: Some(2),
+            ..Default::default()
+        };
+        assert!(matches!(convert(&mtl), Material::Lambertian(_)));
+    }
+
+    #[test]
+    fn reflective_mtl_becomes_metal() {
+        let mtl = tobj::Material {
+            shininess: 1000.0,
+            illumination_model: Some(3),
+            ..Default::default()
+        };
+        match convert(&mtl) {
+            Material::Metal(metal) => assert!(metal.fuzz < 0.05),
+            _ => panic!("Expected a metal"),
+        }
+    }
+
+    #[test]
+    fn transparent_mtl_becomes_dielectric() {
+        let mtl = tobj::Material {
+            dissolve: 0.2,
+            optical_density: 2.4,
+            ..Default::default()
+        };
+        match convert(&mtl) {
+            Material::Dielectric(dielectric) => assert_eq!(2.4, dielectric.ref_idx),
+            _ => panic!("Expected a dielectric"),
+        }
+    }
+
+    #[test]
+    fn missing_mtl_becomes_gray_lambertian() {
+        let mut texture_cache = Cache::new();
+        let material = material(None, "test", Path::new(""), &mut texture_cache).unwrap();
+
+        match material {
+            Material::Lambertian(lambertian) => match &texture_cache[lambertian.texture_idx] {
+                Texture::Solid(solid) => assert_eq!(glam::Vec3::splat(0.5), solid.color),
+                _ => panic!("Expected a solid texture"),
+            },
+            _ => panic!("Expected a lambertian"),
+        }
+    }
+
+    #[test]
+    fn obj_with_a_missing_mtl_file_gets_gray_lambertians() {
+        let path = std::env::temp_dir().join("raytracer_missing_mtl.obj");
+        std::fs::write(
+            &path,
+            "mtllib does_not_exist.mtl\n\
+             v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\n\
+             usemtl red\nf 1 2 3\n\
+             usemtl blue\nf 2 4 3\n",
+        )
+        .unwrap();
+
+        let mut scene = SceneDescription {
+            sky_box: crate::sky_box::SkyBox::Cubemap(crate::sky_box::Cubemap::all(0)),
+            camera: serde_json::from_str(
+                r#"{"pos": [0, 0, 1], "look_at": [0, 0, 0], "vup": [0, 1, 0], "vt_fov": 40, "aspect": 2}"#,
+            )
+            .unwrap(),
+            texture_cache: Cache::new(),
+            geometry_cache: Cache::new(),
+            material_cache: Cache::new(),
+            items: vec![],
+            models: vec![],
+        };
+        import(&path, &mut scene).unwrap();
+
+        // Both faces lose their material so they end up in the same mesh.
+        assert_eq!(1, scene.items.len());
+        match &scene.material_cache[scene.items[0].material_idx] {
+            Material::Lambertian(lambertian) => {
+                match &scene.texture_cache[lambertian.texture_idx] {
+                    Texture::Solid(solid) => assert_eq!(glam::Vec3::splat(0.5), solid.color),
+                    _ => panic!("Expected a solid texture"),
+                }
+            }
+            _ => panic!("Expected a lambertian"),
+        }
+    }
+}
@@ -1,7 +1,72 @@
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+use crate::bvh::Aabb;
+use crate::cache::Cache;
+use crate::geometry::{Geometry, HitInfo, SurfaceSample};
+use crate::primitive::Ray3;
+use crate::transform::Transform;
+
 #[derive(Serialize, Deserialize)]
 pub struct Item {
     pub geometry_idx: usize,
     pub material_idx: usize,
+    /// Places the geometry in the scene. Without it, the geometry is used as
+    /// is. Many items can share one geometry by using different transforms.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+}
+
+impl Item {
+    pub fn hit(
+        &self,
+        geometry_cache: &Cache<Geometry>,
+        ray: &Ray3,
+        tmin: f32,
+        tmax: f32,
+    ) -> Option<HitInfo> {
+        let geometry = &geometry_cache[self.geometry_idx];
+
+        match &self.transform {
+            Some(transform) => geometry
+                .hit(&transform.ray_to_object(ray), tmin, tmax)
+                .map(|hit| transform.hit_to_world(hit)),
+            None => geometry.hit(ray, tmin, tmax),
+        }
+    }
+
+    pub(crate) fn aabb(&self, geometry_cache: &Cache<Geometry>) -> Aabb {
+        let aabb = geometry_cache[self.geometry_idx].aabb();
+
+        match &self.transform {
+            Some(transform) => transform.aabb_to_world(&aabb),
+            None => aabb,
+        }
+    }
+
+    /// Picks a point on the item's surface. The probability density of the
+    /// sample is per unit area of the scene.
+    pub(crate) fn sample_surface<R: Rng>(
+        &self,
+        geometry_cache: &Cache<Geometry>,
+        rng: &mut R,
+    ) -> Option<SurfaceSample> {
+        let sample = geometry_cache[self.geometry_idx].sample_surface(rng)?;
+
+        match &self.transform {
+            Some(transform) => Some(transform.sample_to_world(sample)),
+            None => Some(sample),
+        }
+    }
+
+    /// The probability density with which `sample_surface` picks the point
+    /// which was hit.
+    pub(crate) fn surface_pdf(&self, geometry_cache: &Cache<Geometry>, hit: &HitInfo) -> f32 {
+        let pdf = geometry_cache[self.geometry_idx].surface_pdf();
+
+        match &self.transform {
+            Some(transform) => pdf / transform.area_scale(hit.tbn.n),
+            None => pdf,
+        }
+    }
 }
@@ -1,13 +1,21 @@
+pub mod assets;
 mod bvh;
 pub mod cache;
 pub mod camera;
+pub mod checkpoint;
+pub mod error;
 pub mod geometry;
+pub mod import;
 pub mod item;
 pub mod material;
+mod names;
 pub mod primitive;
 pub mod ray_tracer;
 pub mod scene;
 pub mod sky_box;
+pub mod spectrum;
 pub mod texture;
 pub mod tone_mapper;
+pub mod transform;
 mod util;
+pub mod validation;
@@ -0,0 +1,229 @@
+use glam::Vec3;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+use crate::geometry::HitInfo;
+use crate::primitive::Ray3;
+use crate::texture::Texture;
+
+use super::microfacet::{fresnel_schlick, Frame, Ggx};
+use super::{Bsdf, Interaction, Material};
+
+/// A clear dielectric coat (like varnish or car paint lacquer) over another
+/// material. Light is either reflected off the coat (more of it at grazing
+/// angles) or gets through to the base.
+#[derive(Serialize, Deserialize)]
+pub struct Coated {
+    pub base_idx: usize,
+    /// The refractive index of the coat.
+    pub ref_idx: f32,
+    /// 0 for a mirror-like coat.
+    #[serde(default)]
+    pub roughness: f32,
+}
+
+/// The coat at a hit, seen from the direction `v` (in the frame around the
+/// normal).
+struct Coat {
+    frame: Frame,
+    v: Vec3,
+    f0: Vec3,
+    ggx: Ggx,
+}
+
+impl Coated {
+    pub fn interact<R: Rng>(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        rng: &mut R,
+    ) -> Interaction {
+        let coat = self.coat(ray, hit);
+        let reflectance = coat.reflectance();
+
+        if rng.gen::<f32>() >= reflectance {
+            return material_cache[self.base_idx].interact(
+                material_cache,
+                texture_cache,
+                ray,
+                hit,
+                rng,
+            );
+        }
+
+        let v = coat.v;
+        let (l, attenuation) = if self.roughness > 0.0 {
+            let h = coat.ggx.sample_visible_normal(v, rng);
+            let l = 2.0 * v.dot(h) * h - v;
+            let fresnel = fresnel_schlick(coat.f0, v.dot(h));
+            (
+                l,
+                fresnel * coat.ggx.g2(v, l) / coat.ggx.g1(v) / reflectance,
+            )
+        } else {
+            (Vec3::new(-v.x, -v.y, v.z), Vec3::ONE)
+        };
+
+        if l.z > 0.0 {
+            Interaction::NonTerminal {
+                ray: Ray3::new(hit.pos, coat.frame.to_world(l)),
+                attenuation,
+            }
+        } else {
+            Interaction::Terminal { color: Vec3::ZERO }
+        }
+    }
+
+    /// The coat's reflections plus the base's BSDF, dimmed by the light the
+    /// coat reflects. A mirror-like coat can't be evaluated and neither can a
+    /// coat over a base which can't.
+    pub fn bsdf(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        dir: Vec3,
+    ) -> Option<Bsdf> {
+        if self.roughness <= 0.0 {
+            return None;
+        }
+
+        let base =
+            material_cache[self.base_idx].bsdf(material_cache, texture_cache, ray, hit, dir)?;
+        let coat = self.coat(ray, hit);
+        let reflectance = coat.reflectance();
+        let (value, pdf) = coat.eval(coat.frame.to_local(dir.normalize()));
+
+        Some(Bsdf {
+            value: value + base.value * (1.0 - reflectance),
+            pdf: reflectance * pdf + (1.0 - reflectance) * base.pdf,
+        })
+    }
+
+    /// The light given off by the base which gets through the coat.
+    pub fn emitted(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+    ) -> Vec3 {
+        let emitted =
+            material_cache[self.base_idx].emitted(material_cache, texture_cache, ray, hit);
+        emitted * (1.0 - self.coat(ray, hit).reflectance())
+    }
+
+    fn coat(&self, ray: &Ray3, hit: &HitInfo) -> Coat {
+        let v = -ray.dir.normalize();
+        let normal = hit.tbn.n;
+        // Both sides of the surface look the same.
+        let frame = Frame::new(if normal.dot(v) < 0.0 { -normal } else { normal });
+        let f0 = ((self.ref_idx - 1.0) / (self.ref_idx + 1.0)).powi(2);
+
+        Coat {
+            v: frame.to_local(v),
+            frame,
+            f0: Vec3::splat(f0),
+            ggx: Ggx::new(self.roughness),
+        }
+    }
+}
+
+impl Coat {
+    /// The part of the light which the coat reflects. It's also the
+    /// probability of `interact` reflecting off the coat.
+    fn reflectance(&self) -> f32 {
+        fresnel_schlick(self.f0, self.v.z.abs()).x
+    }
+
+    /// Evaluates the coat's BSDF (with the cosine term) for light arriving
+    /// from `l`, along with the probability density of sampling `l` when
+    /// reflecting off the coat.
+    fn eval(&self, l: Vec3) -> (Vec3, f32) {
+        let v = self.v;
+        if v.z <= 0.0 || l.z <= 0.0 {
+            return (Vec3::ZERO, 0.0);
+        }
+
+        let h = (v + l).normalize();
+        let fresnel = fresnel_schlick(self.f0, v.dot(h));
+        let value = fresnel * self.ggx.d(h) * self.ggx.g2(v, l) / (4.0 * v.z);
+        let pdf = self.ggx.visible_normal_pdf(v, h) / (4.0 * v.dot(h));
+
+        (value, pdf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::material::test_util::{hit, integrate_over_hemisphere};
+    use crate::material::Lambertian;
+    use crate::texture::Solid;
+
+    use super::*;
+
+    #[test]
+    fn sampling_matches_the_bsdf() {
+        let mut texture_cache = Cache::new();
+        let white = texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+        let mut material_cache = Cache::new();
+        let base_idx = material_cache.add(
+            "matte",
+            Material::Lambertian(Lambertian {
+                texture_idx: white,
+                normal_map_idx: None,
+                emission: None,
+            }),
+        );
+
+        let (sin, cos) = 1.0_f32.sin_cos();
+        let ray = Ray3::new(Vec3::new(sin, 0.0, cos), Vec3::new(-sin, 0.0, -cos));
+        let hit = hit(1.0);
+
+        for roughness in [0.0, 0.3] {
+            let coated = Coated {
+                base_idx,
+                ref_idx: 1.5,
+                roughness,
+            };
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+            let num_samples = 200_000;
+
+            let albedo = (0..num_samples)
+                .map(|_| {
+                    match coated.interact(&material_cache, &texture_cache, &ray, &hit, &mut rng) {
+                        Interaction::NonTerminal { attenuation, .. } => attenuation.x,
+                        Interaction::Terminal { .. } => 0.0,
+                    }
+                })
+                .sum::<f32>()
+                / num_samples as f32;
+
+            // A smooth coat over a white base reflects everything.
+            if roughness == 0.0 {
+                assert!((albedo - 1.0).abs() < 0.01, "{}", albedo);
+                continue;
+            }
+
+            let uniform_albedo = integrate_over_hemisphere(num_samples, &mut rng, |dir| {
+                let bsdf = coated.bsdf(&material_cache, &texture_cache, &ray, &hit, dir);
+                bsdf.unwrap().value.x
+            });
+
+            assert!(albedo <= 1.01, "{}", albedo);
+            assert!(
+                (albedo - uniform_albedo).abs() < 0.02,
+                "{} != {}",
+                albedo,
+                uniform_albedo
+            );
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use glam::Vec3;
-use rand::random;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::cache::Cache;
@@ -7,56 +7,164 @@ use crate::geometry::HitInfo;
 use crate::primitive::{Ray3, Vec3Utils};
 use crate::texture::Texture;
 
-use super::Interaction;
+use super::microfacet::{Frame, Ggx};
+use super::{Emission, Interaction};
 
 #[derive(Serialize, Deserialize)]
 pub struct Dielectric {
     pub ref_idx: f32,
     pub normal_map_idx: Option<usize>,
+    /// 0 for clear glass. Rougher glass is frosted: it blurs both what's seen
+    /// through it and its reflections.
+    #[serde(default)]
+    pub roughness: f32,
+    /// Tints the light travelling through the inside. Without it, the glass
+    /// is clear.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub absorption: Option<Absorption>,
+    /// How the refractive index changes with the wavelength. It's only used
+    /// when rendering spectrally. Otherwise `ref_idx` is used for all light.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dispersion: Option<Dispersion>,
+    /// Makes the material glow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emission: Option<Emission>,
+}
+
+/// Absorption that follows the Beer-Lambert law. White light which travels a
+/// distance of `1 / density` through the medium turns into `color`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Absorption {
+    pub color: Vec3,
+    pub density: f32,
+}
+
+impl Absorption {
+    /// The part of the light which is left after travelling `distance`.
+    pub fn transmittance(&self, distance: f32) -> Vec3 {
+        let transmittance = |color: f32| color.max(f32::MIN_POSITIVE).powf(self.density * distance);
+        Vec3::new(
+            transmittance(self.color.x),
+            transmittance(self.color.y),
+            transmittance(self.color.z),
+        )
+    }
+}
+
+/// A model of the refractive index as a function of the wavelength λ in
+/// micrometers. Glass catalogs list the coefficients of both.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Dispersion {
+    /// n = a + b / λ². It's accurate enough for most glasses in the visible
+    /// range. BK7 glass has a = 1.5046 and b = 0.0042.
+    Cauchy { a: f32, b: f32 },
+    /// n² = 1 + Σ b λ² / (λ² - c). BK7 glass has b = [1.0396, 0.2318, 1.0105]
+    /// and c = [0.0060, 0.0200, 103.56].
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    /// Returns the refractive index for light of a wavelength in nanometers.
+    pub fn ref_idx(&self, wavelength: f32) -> f32 {
+        let micrometers = wavelength / 1000.0;
+        let sq = micrometers * micrometers;
+
+        match self {
+            Self::Cauchy { a, b } => a + b / sq,
+            Self::Sellmeier { b, c } => {
+                let sum: f32 = b.iter().zip(c).map(|(b, c)| b * sq / (sq - c)).sum();
+                (1.0 + sum).sqrt()
+            }
+        }
+    }
 }
 
 impl Dielectric {
-    pub fn interact(
+    pub fn interact<R: Rng>(
         &self,
         texture_cache: &Cache<Texture>,
         ray: &Ray3,
         hit: &HitInfo,
+        rng: &mut R,
     ) -> Interaction {
         let normal = self
             .normal_map_idx
-            .map(|idx| texture_cache[idx].normal(hit.u, hit.v, hit.tbn.matrix()))
+            .map(|idx| {
+                texture_cache[idx].normal(texture_cache, hit.tex_coord(ray), hit.tbn.matrix())
+            })
             .unwrap_or(hit.tbn.n);
 
-        let outward_normal;
+        // Light of other wavelengths would take other paths, so only the hero
+        // wavelength is followed.
+        let mut wavelengths = ray.wavelengths;
+        let mut dispersion_weight = Vec3::ONE;
+        let ref_idx = match (&self.dispersion, &mut wavelengths) {
+            (Some(dispersion), Some(wavelengths)) => {
+                dispersion_weight = wavelengths.keep_hero();
+                dispersion.ref_idx(wavelengths.hero)
+            }
+            _ => self.ref_idx,
+        };
+
+        let inside = ray.dir.dot(normal) > 0.0;
+        let outward_normal = if inside { -normal } else { normal };
+
+        // Rough glass is made of tiny smooth facets. The ray is refracted or
+        // reflected by one of the facets it can see.
+        let rough = if self.roughness > 0.0 {
+            let frame = Frame::new(outward_normal);
+            let ggx = Ggx::new(self.roughness);
+            let facet_normal = ggx.sample_visible_normal(frame.to_local(-ray.dir.normalize()), rng);
+            Some((frame, ggx, facet_normal))
+        } else {
+            None
+        };
+        let facet_normal = match &rough {
+            Some((frame, _, facet_normal)) => frame.to_world(*facet_normal),
+            None => outward_normal,
+        };
+
         let ni_by_nt;
         let cos;
 
-        if ray.dir.dot(normal) > 0.0 {
-            outward_normal = -normal;
-            ni_by_nt = self.ref_idx;
-            cos = self.ref_idx * ray.dir.normalize().dot(normal);
+        if inside {
+            ni_by_nt = ref_idx;
+            cos = -ref_idx * ray.dir.normalize().dot(facet_normal);
         } else {
-            outward_normal = normal;
-            ni_by_nt = 1.0 / self.ref_idx;
-            cos = -ray.dir.normalize().dot(normal);
+            ni_by_nt = 1.0 / ref_idx;
+            cos = -ray.dir.normalize().dot(facet_normal);
         }
 
-        if let Some(refraction_dir) = ray.dir.refract(outward_normal, ni_by_nt) {
-            let reflection_probability = schlick(cos, self.ref_idx);
+        let (scattered_dir, refracted) = match ray.dir.refract(facet_normal, ni_by_nt) {
+            Some(refraction_dir) if rng.gen::<f32>() > schlick(cos, ref_idx) => {
+                (refraction_dir, true)
+            }
+            _ => (ray.dir.reflect(facet_normal), false),
+        };
+
+        // The light was absorbed along the way from where it entered.
+        let mut attenuation = match (&self.absorption, inside) {
+            (Some(absorption), true) => absorption.transmittance(hit.t * ray.dir.length()),
+            _ => Vec3::ONE,
+        } * dispersion_weight;
 
-            if random::<f32>() > reflection_probability {
-                return Interaction::NonTerminal {
-                    ray: Ray3::new(hit.pos, refraction_dir),
-                    attenuation: Vec3::ONE,
-                };
+        if let Some((frame, ggx, _)) = &rough {
+            // Facets hide some of the scattered light. Light scattered to
+            // the wrong side of the surface is lost.
+            let scattered_dir = frame.to_local(scattered_dir.normalize());
+
+            if refracted != (scattered_dir.z < 0.0) {
+                return Interaction::Terminal { color: Vec3::ZERO };
             }
+            attenuation *= ggx.g1(scattered_dir);
         }
 
-        let reflection_dir = ray.dir.reflect(normal);
+        let mut scattered_ray = Ray3::new(hit.pos, scattered_dir);
+        scattered_ray.wavelengths = wavelengths;
 
         Interaction::NonTerminal {
-            ray: Ray3::new(hit.pos, reflection_dir),
-            attenuation: Vec3::ONE,
+            ray: scattered_ray,
+            attenuation,
         }
     }
 }
@@ -66,3 +174,125 @@ fn schlick(cos: f32, ref_idx: f32) -> f32 {
     let r0 = r0.powi(2);
     r0 + (1.0 - r0) * (1.0 - cos).powi(5)
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::material::test_util::hit;
+    use crate::spectrum::Wavelengths;
+
+    use super::*;
+
+    fn glass(roughness: f32, absorption: Option<Absorption>) -> Dielectric {
+        Dielectric {
+            ref_idx: 1.5,
+            normal_map_idx: None,
+            roughness,
+            absorption,
+            dispersion: None,
+            emission: None,
+        }
+    }
+
+    fn attenuations(dielectric: &Dielectric, ray: &Ray3, hit: &HitInfo) -> Vec<Vec3> {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        (0..10_000)
+            .map(
+                |_| match dielectric.interact(&Cache::new(), ray, hit, &mut rng) {
+                    Interaction::NonTerminal { attenuation, .. } => attenuation,
+                    Interaction::Terminal { color } => color,
+                },
+            )
+            .collect()
+    }
+
+    #[test]
+    fn light_leaving_the_inside_is_absorbed_along_the_way() {
+        let absorption = Absorption {
+            color: Vec3::new(0.8, 0.5, 0.2),
+            density: 0.5,
+        };
+        let dielectric = glass(0.0, Some(absorption.clone()));
+
+        // Travelling 2 units (1 / density) turns white into the color.
+        let leaving = Ray3::new(Vec3::new(0.0, 0.0, -1.0), Vec3::Z);
+        for attenuation in attenuations(&dielectric, &leaving, &hit(2.0)) {
+            assert!(attenuation.abs_diff_eq(absorption.color, 1e-5));
+        }
+
+        // Light entering the glass hasn't travelled through it yet.
+        let entering = Ray3::new(Vec3::Z, -Vec3::Z);
+        for attenuation in attenuations(&dielectric, &entering, &hit(2.0)) {
+            assert_eq!(Vec3::ONE, attenuation);
+        }
+    }
+
+    #[test]
+    fn bk7_dispersion_models_agree() {
+        let cauchy = Dispersion::Cauchy {
+            a: 1.5046,
+            b: 0.0042,
+        };
+        let sellmeier = Dispersion::Sellmeier {
+            b: [1.039_612, 0.231_792_3, 1.010_469_4],
+            c: [0.006_000_7, 0.020_017_914, 103.560_65],
+        };
+
+        assert!((sellmeier.ref_idx(587.6) - 1.5168).abs() < 1e-4);
+        for wavelength in [400.0, 500.0, 600.0, 700.0] {
+            assert!((cauchy.ref_idx(wavelength) - sellmeier.ref_idx(wavelength)).abs() < 2e-3);
+        }
+        // Blue light is bent more than red light.
+        assert!(sellmeier.ref_idx(450.0) > sellmeier.ref_idx(650.0));
+    }
+
+    #[test]
+    fn dispersion_bends_wavelengths_differently() {
+        let dielectric = Dielectric {
+            dispersion: Some(Dispersion::Cauchy { a: 1.5, b: 0.05 }),
+            ..glass(0.0, None)
+        };
+        let refracted = |hero| {
+            let mut ray = Ray3::new(Vec3::new(-1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, -1.0));
+            ray.wavelengths = Some(Wavelengths {
+                hero,
+                hero_only: false,
+            });
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(3);
+
+            loop {
+                match dielectric.interact(&Cache::new(), &ray, &hit(1.0), &mut rng) {
+                    Interaction::NonTerminal { ray, .. } if ray.dir.z < 0.0 => return ray,
+                    _ => continue,
+                }
+            }
+        };
+
+        let blue = refracted(450.0);
+        let red = refracted(650.0);
+
+        assert!(blue.wavelengths.unwrap().hero_only);
+        // Blue light is bent closer to the normal.
+        assert!(blue.dir.normalize().x < red.dir.normalize().x);
+    }
+
+    #[test]
+    fn rough_glass_loses_little_light() {
+        let entering = Ray3::new(Vec3::new(0.3, 0.0, 1.0), Vec3::new(-0.3, 0.0, -1.0));
+
+        for roughness in [0.1, 0.4] {
+            let attenuations = attenuations(&glass(roughness, None), &entering, &hit(1.0));
+            let mean = attenuations
+                .iter()
+                .map(|attenuation| attenuation.x)
+                .sum::<f32>()
+                / attenuations.len() as f32;
+
+            assert!(attenuations.iter().all(|attenuation| attenuation.x <= 1.0));
+            assert!(mean > 0.9, "{} for roughness {}", mean, roughness);
+        }
+    }
+}
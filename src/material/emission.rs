@@ -0,0 +1,22 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+use crate::geometry::HitInfo;
+use crate::primitive::Ray3;
+use crate::texture::Texture;
+
+/// Light given off by a material on top of the light it scatters, like a
+/// glowing screen or hot metal. Unlike lights, emissive materials aren't
+/// sampled directly, so they're best kept small or dim.
+#[derive(Serialize, Deserialize)]
+pub struct Emission {
+    pub texture_idx: usize,
+    pub strength: f32,
+}
+
+impl Emission {
+    pub fn color(&self, texture_cache: &Cache<Texture>, ray: &Ray3, hit: &HitInfo) -> Vec3 {
+        texture_cache[self.texture_idx].color(texture_cache, hit.tex_coord(ray)) * self.strength
+    }
+}
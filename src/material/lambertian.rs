@@ -1,3 +1,7 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::cache::Cache;
@@ -5,26 +9,98 @@ use crate::geometry::HitInfo;
 use crate::primitive::Ray3;
 use crate::texture::Texture;
 
-use super::util::rand_pos_in_sphere;
-use super::Interaction;
+use super::util::rand_unit_vector;
+use super::{Bsdf, Emission, Interaction};
 
 #[derive(Serialize, Deserialize)]
 pub struct Lambertian {
     pub texture_idx: usize,
     pub normal_map_idx: Option<usize>,
+    /// Makes the material glow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emission: Option<Emission>,
 }
 
 impl Lambertian {
-    pub fn interact(&self, texture_cache: &Cache<Texture>, hit: &HitInfo) -> Interaction {
-        let normal = self
-            .normal_map_idx
-            .map(|idx| texture_cache[idx].normal(hit.u, hit.v, hit.tbn.matrix()))
-            .unwrap_or(hit.tbn.n);
+    /// Scatters rays with a cosine weighted distribution about the normal.
+    pub fn interact<R: Rng>(
+        &self,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        rng: &mut R,
+    ) -> Interaction {
+        let normal = self.normal(texture_cache, ray, hit);
 
-        let target = hit.pos + normal + rand_pos_in_sphere(1.0);
+        let dir = normal + rand_unit_vector(rng);
         Interaction::NonTerminal {
-            ray: Ray3::new(hit.pos, target - hit.pos),
-            attenuation: texture_cache[self.texture_idx].color(hit.u, hit.v),
+            // The random vector can cancel the normal out.
+            ray: Ray3::new(hit.pos, if dir == Vec3::ZERO { normal } else { dir }),
+            attenuation: texture_cache[self.texture_idx].color(texture_cache, hit.tex_coord(ray)),
+        }
+    }
+
+    pub fn bsdf(
+        &self,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        dir: Vec3,
+    ) -> Bsdf {
+        let cos = self
+            .normal(texture_cache, ray, hit)
+            .dot(dir.normalize())
+            .max(0.0);
+
+        Bsdf {
+            value: texture_cache[self.texture_idx].color(texture_cache, hit.tex_coord(ray)) * cos
+                / PI,
+            pdf: cos / PI,
+        }
+    }
+
+    fn normal(&self, texture_cache: &Cache<Texture>, ray: &Ray3, hit: &HitInfo) -> Vec3 {
+        self.normal_map_idx
+            .map(|idx| {
+                texture_cache[idx].normal(texture_cache, hit.tex_coord(ray), hit.tbn.matrix())
+            })
+            .unwrap_or(hit.tbn.n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::test_util::hit;
+    use crate::texture::Solid;
+
+    use super::*;
+
+    #[test]
+    fn sampled_rays_are_weighted_like_the_bsdf() {
+        let mut texture_cache = Cache::new();
+        let color = Vec3::new(0.2, 0.4, 0.8);
+        let texture_idx = texture_cache.add("color", Texture::Solid(Solid { color }));
+        let lambertian = Lambertian {
+            texture_idx,
+            normal_map_idx: None,
+            emission: None,
+        };
+        let hit = hit(1.0);
+
+        let ray = Ray3::new(Vec3::Z, -Vec3::Z);
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            match lambertian.interact(&texture_cache, &ray, &hit, &mut rng) {
+                Interaction::NonTerminal {
+                    ray: scattered_ray,
+                    attenuation,
+                } => {
+                    let bsdf = lambertian.bsdf(&texture_cache, &ray, &hit, scattered_ray.dir);
+                    assert!((bsdf.value / bsdf.pdf).abs_diff_eq(attenuation, 1e-4));
+                }
+                Interaction::Terminal { .. } => panic!("Expected a scattered ray"),
+            }
         }
     }
 }
@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::cache::Cache;
 use crate::geometry::HitInfo;
+use crate::primitive::Ray3;
 use crate::texture::Texture;
 
 use super::Interaction;
@@ -11,12 +12,59 @@ use super::Interaction;
 pub struct Light {
     pub texture_idx: usize,
     pub brightness: Vec3,
+    /// Only emits light to the side the normal points to. Planes lit from
+    /// one side only are this way.
+    #[serde(default)]
+    pub one_sided: bool,
 }
 
 impl Light {
-    pub fn interact(&self, texture_cache: &Cache<Texture>, hit: &HitInfo) -> Interaction {
+    pub fn interact(
+        &self,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+    ) -> Interaction {
+        if self.one_sided && ray.dir.dot(hit.tbn.n) > 0.0 {
+            return Interaction::Terminal { color: Vec3::ZERO };
+        }
+
         Interaction::Terminal {
-            color: texture_cache[self.texture_idx].color(hit.u, hit.v) * self.brightness,
+            color: texture_cache[self.texture_idx].color(texture_cache, hit.tex_coord(ray))
+                * self.brightness,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::material::test_util::hit;
+    use crate::texture::Solid;
+
+    use super::*;
+
+    #[test]
+    fn one_sided_lights_only_emit_along_their_normal() {
+        let mut texture_cache = Cache::new();
+        let texture_idx = texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+        let hit = hit(1.0);
+
+        let color = |one_sided, ray: &Ray3| {
+            let light = Light {
+                texture_idx,
+                brightness: Vec3::splat(2.0),
+                one_sided,
+            };
+            match light.interact(&texture_cache, ray, &hit) {
+                Interaction::Terminal { color } => color,
+                Interaction::NonTerminal { .. } => panic!("Expected the light's color"),
+            }
+        };
+        let front = Ray3::new(Vec3::Z, -Vec3::Z);
+        let back = Ray3::new(-Vec3::Z, Vec3::Z);
+
+        assert_eq!(Vec3::splat(2.0), color(false, &back));
+        assert_eq!(Vec3::splat(2.0), color(true, &front));
+        assert_eq!(Vec3::ZERO, color(true, &back));
+    }
+}
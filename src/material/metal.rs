@@ -1,4 +1,7 @@
+use std::f32::consts::PI;
+
 use glam::Vec3;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::cache::Cache;
@@ -6,37 +9,132 @@ use crate::geometry::HitInfo;
 use crate::primitive::{Ray3, Vec3Utils};
 use crate::texture::Texture;
 
-use super::{util::rand_pos_in_sphere, Interaction};
+use super::{util::rand_pos_in_sphere, Bsdf, Emission, Interaction};
 
 #[derive(Serialize, Deserialize)]
 pub struct Metal {
     pub texture_idx: usize,
     pub normal_map_idx: Option<usize>,
     pub fuzz: f32,
+    /// Makes the material glow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emission: Option<Emission>,
 }
 
 impl Metal {
-    pub fn interact(
+    pub fn interact<R: Rng>(
         &self,
         texture_cache: &Cache<Texture>,
         ray: &Ray3,
         hit: &HitInfo,
+        rng: &mut R,
     ) -> Interaction {
-        let normal = self
-            .normal_map_idx
-            .map(|idx| texture_cache[idx].normal(hit.u, hit.v, hit.tbn.matrix()))
-            .unwrap_or(hit.tbn.n);
+        let normal = self.normal(texture_cache, ray, hit);
 
         let reflected_dir = ray.dir.normalize().reflect(normal);
-        let scattered_ray = Ray3::new(hit.pos, reflected_dir + rand_pos_in_sphere(self.fuzz));
+        let scattered_ray = Ray3::new(hit.pos, reflected_dir + rand_pos_in_sphere(rng, self.fuzz));
 
         if scattered_ray.dir.dot(normal) > 0.0 {
             Interaction::NonTerminal {
                 ray: scattered_ray,
-                attenuation: texture_cache[self.texture_idx].color(hit.u, hit.v),
+                attenuation: texture_cache[self.texture_idx]
+                    .color(texture_cache, hit.tex_coord(ray)),
             }
         } else {
             Interaction::Terminal { color: Vec3::ZERO }
         }
     }
+
+    /// Scattered rays are weighted by the texture's color alone, so the BSDF
+    /// (with the cosine term) is the color times the probability density of
+    /// scattering. A metal without fuzz is a perfect mirror and can't be
+    /// evaluated.
+    pub fn bsdf(
+        &self,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        dir: Vec3,
+    ) -> Option<Bsdf> {
+        if self.fuzz <= 0.0 {
+            return None;
+        }
+
+        let normal = self.normal(texture_cache, ray, hit);
+        let reflected_dir = ray.dir.normalize().reflect(normal);
+
+        let pdf = if dir.dot(normal) > 0.0 {
+            fuzzy_dir_pdf(reflected_dir, self.fuzz, dir.normalize())
+        } else {
+            0.0
+        };
+
+        Some(Bsdf {
+            value: texture_cache[self.texture_idx].color(texture_cache, hit.tex_coord(ray)) * pdf,
+            pdf,
+        })
+    }
+
+    fn normal(&self, texture_cache: &Cache<Texture>, ray: &Ray3, hit: &HitInfo) -> Vec3 {
+        self.normal_map_idx
+            .map(|idx| {
+                texture_cache[idx].normal(texture_cache, hit.tex_coord(ray), hit.tbn.matrix())
+            })
+            .unwrap_or(hit.tbn.n)
+    }
+}
+
+/// Returns the probability density (per unit solid angle) of `dir` when
+/// directions are picked by adding a random point in a sphere of radius `fuzz`
+/// to the unit vector `center`.
+///
+/// The density is the volume of the sphere inside a thin cone around `dir`,
+/// which is an integral of s² along the part of the cone's axis inside the
+/// sphere.
+fn fuzzy_dir_pdf(center: Vec3, fuzz: f32, dir: Vec3) -> f32 {
+    // The axis enters and leaves the sphere where
+    // s² - 2s (dir · center) + 1 - fuzz² = 0.
+    let b = dir.dot(center);
+    let discriminant = b * b - 1.0 + fuzz * fuzz;
+
+    if discriminant <= 0.0 {
+        return 0.0;
+    }
+
+    let s_near = (b - discriminant.sqrt()).max(0.0);
+    let s_far = b + discriminant.sqrt();
+
+    if s_far <= 0.0 {
+        return 0.0;
+    }
+
+    (s_far.powi(3) - s_near.powi(3)) / (4.0 * PI * fuzz.powi(3))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::material::util::rand_unit_vector;
+
+    use super::*;
+
+    #[test]
+    fn fuzzy_dir_pdf_integrates_to_one() {
+        let mut rng = rand::thread_rng();
+
+        for fuzz in [0.3, 1.0, 2.5] {
+            // Monte Carlo estimate of the integral over the unit sphere.
+            let num_samples = 200_000;
+            let sum: f32 = (0..num_samples)
+                .map(|_| fuzzy_dir_pdf(Vec3::Y, fuzz, rand_unit_vector(&mut rng)))
+                .sum();
+            let integral = 4.0 * PI * sum / num_samples as f32;
+
+            assert!(
+                (integral - 1.0).abs() < 0.05,
+                "{} for fuzz {}",
+                integral,
+                fuzz
+            );
+        }
+    }
 }
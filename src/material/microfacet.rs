@@ -0,0 +1,164 @@
+//! Building blocks of materials whose surfaces are made of tiny mirror-like
+//! facets. Directions are in a local frame whose z axis is the surface's
+//! normal.
+
+use std::f32::consts::{PI, TAU};
+
+use glam::Vec3;
+use rand::Rng;
+
+/// Keeps perfectly smooth surfaces from having an infinitely sharp highlight.
+const MIN_ALPHA: f32 = 1e-3;
+
+/// An orthonormal basis around a normal.
+pub(super) struct Frame {
+    t: Vec3,
+    b: Vec3,
+    n: Vec3,
+}
+
+impl Frame {
+    pub fn new(n: Vec3) -> Self {
+        let (t, b) = n.any_orthonormal_pair();
+        Frame { t, b, n }
+    }
+
+    pub fn to_local(&self, dir: Vec3) -> Vec3 {
+        Vec3::new(dir.dot(self.t), dir.dot(self.b), dir.dot(self.n))
+    }
+
+    pub fn to_world(&self, dir: Vec3) -> Vec3 {
+        dir.x * self.t + dir.y * self.b + dir.z * self.n
+    }
+}
+
+/// The Trowbridge-Reitz (GGX) distribution of facet normals, with Smith's
+/// height-correlated shadowing and masking.
+pub(super) struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    /// Roughness goes from 0 (a mirror) to 1. It's squared, so that the
+    /// look changes evenly along the range.
+    pub fn new(roughness: f32) -> Self {
+        Ggx {
+            alpha: (roughness * roughness).max(MIN_ALPHA),
+        }
+    }
+
+    /// The density of facets with the normal `h`, per unit area of the surface
+    /// and per unit solid angle.
+    pub fn d(&self, h: Vec3) -> f32 {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+
+        let alpha_sq = self.alpha * self.alpha;
+        let t = h.z * h.z * (alpha_sq - 1.0) + 1.0;
+        alpha_sq / (PI * t * t)
+    }
+
+    /// Smith's auxiliary function. The part of the facets facing `w` which
+    /// other facets hide is `lambda / (1 + lambda)`.
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos_sq = w.z * w.z;
+        if cos_sq <= 0.0 {
+            return f32::INFINITY;
+        }
+
+        let tan_sq = (1.0 - cos_sq) / cos_sq;
+        ((1.0 + self.alpha * self.alpha * tan_sq).sqrt() - 1.0) / 2.0
+    }
+
+    /// The part of the facets which is visible from `w`.
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// The part of the facets which is visible from both `v` and `l`.
+    pub fn g2(&self, v: Vec3, l: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(v) + self.lambda(l))
+    }
+
+    /// Picks the normal of a facet that's visible from `v` (above the
+    /// surface), weighted by how much of the view the facet takes up. It's
+    /// Eric Heitz's "Sampling the GGX Distribution of Visible Normals".
+    pub fn sample_visible_normal<R: Rng>(&self, v: Vec3, rng: &mut R) -> Vec3 {
+        // Stretch the view so that the facets become a hemisphere.
+        let v_hemisphere = Vec3::new(self.alpha * v.x, self.alpha * v.y, v.z).normalize();
+
+        let len_sq = v_hemisphere.x * v_hemisphere.x + v_hemisphere.y * v_hemisphere.y;
+        let t1 = if len_sq > 0.0 {
+            Vec3::new(-v_hemisphere.y, v_hemisphere.x, 0.0) / len_sq.sqrt()
+        } else {
+            Vec3::X
+        };
+        let t2 = v_hemisphere.cross(t1);
+
+        // A point on the disk that the visible half of the hemisphere
+        // projects onto.
+        let r = rng.gen::<f32>().sqrt();
+        let (sin, cos) = (TAU * rng.gen::<f32>()).sin_cos();
+        let p1 = r * cos;
+        let s = (1.0 + v_hemisphere.z) / 2.0;
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * sin;
+
+        let n_hemisphere =
+            p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * v_hemisphere;
+
+        Vec3::new(
+            self.alpha * n_hemisphere.x,
+            self.alpha * n_hemisphere.y,
+            n_hemisphere.z.max(0.0),
+        )
+        .normalize()
+    }
+
+    /// The probability density (per unit solid angle) with which
+    /// `sample_visible_normal` picks `h` for the view `v`.
+    pub fn visible_normal_pdf(&self, v: Vec3, h: Vec3) -> f32 {
+        if v.z <= 0.0 {
+            return 0.0;
+        }
+
+        self.g1(v) * v.dot(h).max(0.0) * self.d(h) / v.z
+    }
+}
+
+/// Schlick's approximation of the part of the light which is reflected, for
+/// light at an angle with the cosine `cos` to the facet. `f0` is the part
+/// reflected straight back.
+pub(super) fn fresnel_schlick(f0: Vec3, cos: f32) -> Vec3 {
+    f0 + (Vec3::ONE - f0) * (1.0 - cos).clamp(0.0, 1.0).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::material::test_util::integrate_over_hemisphere;
+
+    use super::*;
+
+    #[test]
+    fn projected_facets_cover_the_surface() {
+        // The integral of D(h) cos(h) over the hemisphere is 1.
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        for roughness in [0.3, 0.6, 1.0] {
+            let ggx = Ggx::new(roughness);
+            let num_samples = 200_000;
+
+            let integral = integrate_over_hemisphere(num_samples, &mut rng, |h| ggx.d(h) * h.z);
+
+            assert!(
+                (integral - 1.0).abs() < 0.03,
+                "{} for roughness {}",
+                integral,
+                roughness
+            );
+        }
+    }
+}
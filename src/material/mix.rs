@@ -0,0 +1,160 @@
+use glam::Vec3;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+use crate::geometry::HitInfo;
+use crate::primitive::Ray3;
+use crate::texture::Texture;
+
+use super::{Bsdf, Interaction, Material};
+
+/// A blend of two other materials, like rust over metal. Every interaction
+/// picks one of them at random, so they can be any materials (including other
+/// mixes).
+#[derive(Serialize, Deserialize)]
+pub struct Mix {
+    pub base_idx: usize,
+    pub layer_idx: usize,
+    /// How much of the layer covers the base, from 0 to 1.
+    pub amount: f32,
+    /// Multiplies `amount` by the average of the texture's channels (read as
+    /// linear data), so that the layer only covers parts of the surface.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mask_idx: Option<usize>,
+}
+
+impl Mix {
+    pub fn interact<R: Rng>(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        rng: &mut R,
+    ) -> Interaction {
+        let idx = if rng.gen::<f32>() < self.amount(texture_cache, ray, hit) {
+            self.layer_idx
+        } else {
+            self.base_idx
+        };
+
+        material_cache[idx].interact(material_cache, texture_cache, ray, hit, rng)
+    }
+
+    /// The blend of the BSDFs of both materials. It can only be evaluated if
+    /// both can.
+    pub fn bsdf(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        dir: Vec3,
+    ) -> Option<Bsdf> {
+        let bsdf_of =
+            |idx: usize| material_cache[idx].bsdf(material_cache, texture_cache, ray, hit, dir);
+        let base = bsdf_of(self.base_idx)?;
+        let layer = bsdf_of(self.layer_idx)?;
+        let amount = self.amount(texture_cache, ray, hit);
+
+        Some(Bsdf {
+            value: base.value.lerp(layer.value, amount),
+            pdf: base.pdf + (layer.pdf - base.pdf) * amount,
+        })
+    }
+
+    /// The blend of the light given off by both materials.
+    pub fn emitted(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+    ) -> Vec3 {
+        let emitted_by =
+            |idx: usize| material_cache[idx].emitted(material_cache, texture_cache, ray, hit);
+        emitted_by(self.base_idx).lerp(
+            emitted_by(self.layer_idx),
+            self.amount(texture_cache, ray, hit),
+        )
+    }
+
+    fn amount(&self, texture_cache: &Cache<Texture>, ray: &Ray3, hit: &HitInfo) -> f32 {
+        let mask = self
+            .mask_idx
+            .map(|idx| {
+                let data = texture_cache[idx].data(texture_cache, hit.tex_coord(ray));
+                data.dot(Vec3::ONE) / 3.0
+            })
+            .unwrap_or(1.0);
+
+        (self.amount * mask).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::material::test_util::hit;
+    use crate::material::Lambertian;
+    use crate::texture::Solid;
+
+    use super::*;
+
+    #[test]
+    fn layer_covers_the_base_by_the_masked_amount() {
+        let mut texture_cache = Cache::new();
+        let mut solid = |name, color| texture_cache.add(name, Texture::Solid(Solid { color }));
+        let red = solid("red", Vec3::X);
+        let blue = solid("blue", Vec3::Z);
+        let half = solid("half", Vec3::splat(0.5));
+
+        let mut material_cache = Cache::new();
+        let mut matte = |name, texture_idx| {
+            material_cache.add(
+                name,
+                Material::Lambertian(Lambertian {
+                    texture_idx,
+                    normal_map_idx: None,
+                    emission: None,
+                }),
+            )
+        };
+        let rust = matte("rust", red);
+        let paint = matte("paint", blue);
+
+        let mix = Mix {
+            base_idx: paint,
+            layer_idx: rust,
+            amount: 0.5,
+            mask_idx: Some(half),
+        };
+        let ray = Ray3::new(Vec3::Z, -Vec3::Z);
+        let hit = hit(1.0);
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        let num_samples = 20_000;
+        let mean = (0..num_samples).fold(Vec3::ZERO, |sum, _| {
+            match mix.interact(&material_cache, &texture_cache, &ray, &hit, &mut rng) {
+                Interaction::NonTerminal { attenuation, .. } => sum + attenuation,
+                Interaction::Terminal { .. } => sum,
+            }
+        }) / num_samples as f32;
+
+        assert!(
+            mean.abs_diff_eq(Vec3::new(0.25, 0.0, 0.75), 0.02),
+            "{}",
+            mean
+        );
+
+        let bsdf = mix
+            .bsdf(&material_cache, &texture_cache, &ray, &hit, Vec3::Z)
+            .unwrap();
+        assert!(bsdf
+            .value
+            .abs_diff_eq(Vec3::new(0.25, 0.0, 0.75) * bsdf.pdf, 1e-6));
+    }
+}
@@ -1,27 +1,40 @@
+mod coated;
 mod dielectric;
+mod emission;
 mod lambertian;
 mod light;
 mod metal;
+mod microfacet;
+mod mix;
+mod principled;
 mod util;
 
 use crate::cache::Cache;
 use crate::geometry::HitInfo;
-use crate::primitive::Ray3;
+use crate::primitive::{Ray3, RayCone};
 use crate::texture::Texture;
 
-pub use dielectric::Dielectric;
+pub use coated::Coated;
+pub use dielectric::{Absorption, Dielectric, Dispersion};
+pub use emission::Emission;
 use glam::Vec3;
 pub use lambertian::Lambertian;
 pub use light::Light;
 pub use metal::Metal;
+pub use mix::Mix;
+pub use principled::Principled;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
 pub enum Material {
+    Coated(Coated),
     Dielectric(Dielectric),
     Lambertian(Lambertian),
     Light(Light),
     Metal(Metal),
+    Mix(Mix),
+    Principled(Principled),
 }
 
 pub enum Interaction {
@@ -29,18 +42,156 @@ pub enum Interaction {
     Terminal { color: Vec3 },
 }
 
+/// A material's response to light arriving from a given direction.
+pub struct Bsdf {
+    /// The BSDF multiplied by the cosine of the angle between the direction
+    /// and the normal.
+    pub value: Vec3,
+    /// The probability density (per unit solid angle) with which `interact`
+    /// scatters rays in the direction.
+    pub pdf: f32,
+}
+
 impl Material {
-    pub fn interact(
+    /// All the randomness used while interacting comes from `rng`, so seeded
+    /// RNGs give reproducible results. Materials made of other materials look
+    /// them up in `material_cache`.
+    pub fn interact<R: Rng>(
         &self,
+        material_cache: &Cache<Material>,
         texture_cache: &Cache<Texture>,
         ray: &Ray3,
         hit: &HitInfo,
+        rng: &mut R,
     ) -> Interaction {
+        let mut interaction = match self {
+            Material::Coated(mat) => mat.interact(material_cache, texture_cache, ray, hit, rng),
+            Material::Dielectric(mat) => mat.interact(texture_cache, ray, hit, rng),
+            Material::Lambertian(mat) => mat.interact(texture_cache, ray, hit, rng),
+            Material::Light(mat) => mat.interact(texture_cache, ray, hit),
+            Material::Metal(mat) => mat.interact(texture_cache, ray, hit, rng),
+            Material::Mix(mat) => mat.interact(material_cache, texture_cache, ray, hit, rng),
+            Material::Principled(mat) => mat.interact(texture_cache, ray, hit, rng),
+        };
+
+        // Scattering is instantaneous. The scattered ray's cone starts as wide
+        // as the incoming one was at the hit. It carries the same wavelengths
+        // unless the material dropped some.
+        if let Interaction::NonTerminal {
+            ray: scattered_ray, ..
+        } = &mut interaction
+        {
+            scattered_ray.time = ray.time;
+            scattered_ray.cone = RayCone {
+                width: ray.cone.width_at(hit.t * ray.dir.length()),
+                spread: ray.cone.spread,
+            };
+            if scattered_ray.wavelengths.is_none() {
+                scattered_ray.wavelengths = ray.wavelengths;
+            }
+        }
+
+        interaction
+    }
+
+    /// Evaluates the BSDF for light which arrives along `dir` and leaves
+    /// against `ray`. Materials which only scatter in discrete directions
+    /// (or don't scatter at all) can't be evaluated and return `None`.
+    pub fn bsdf(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        dir: Vec3,
+    ) -> Option<Bsdf> {
+        match self {
+            Material::Coated(mat) => mat.bsdf(material_cache, texture_cache, ray, hit, dir),
+            Material::Dielectric(_) | Material::Light(_) => None,
+            Material::Lambertian(mat) => Some(mat.bsdf(texture_cache, ray, hit, dir)),
+            Material::Metal(mat) => mat.bsdf(texture_cache, ray, hit, dir),
+            Material::Mix(mat) => mat.bsdf(material_cache, texture_cache, ray, hit, dir),
+            Material::Principled(mat) => Some(mat.bsdf(texture_cache, ray, hit, dir)),
+        }
+    }
+
+    /// The light given off by emissive materials where `ray` hit them. Lights
+    /// aren't included, since their light ends the path in `interact`.
+    pub fn emitted(
+        &self,
+        material_cache: &Cache<Material>,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+    ) -> Vec3 {
+        let emission = match self {
+            Material::Coated(mat) => return mat.emitted(material_cache, texture_cache, ray, hit),
+            Material::Dielectric(mat) => &mat.emission,
+            Material::Lambertian(mat) => &mat.emission,
+            Material::Light(_) => return Vec3::ZERO,
+            Material::Metal(mat) => &mat.emission,
+            Material::Mix(mat) => return mat.emitted(material_cache, texture_cache, ray, hit),
+            Material::Principled(mat) => &mat.emission,
+        };
+
+        emission
+            .as_ref()
+            .map(|emission| emission.color(texture_cache, ray, hit))
+            .unwrap_or(Vec3::ZERO)
+    }
+
+    /// Returns the fields of materials which are made of other materials,
+    /// along with the indices of those materials.
+    pub fn material_idxs(&self) -> Vec<(&'static str, usize)> {
         match self {
-            Material::Dielectric(mat) => mat.interact(texture_cache, ray, hit),
-            Material::Lambertian(mat) => mat.interact(texture_cache, hit),
-            Material::Light(mat) => mat.interact(texture_cache, hit),
-            Material::Metal(mat) => mat.interact(texture_cache, ray, hit),
+            Material::Coated(coated) => vec![("Coated.base_idx", coated.base_idx)],
+            Material::Mix(mix) => vec![
+                ("Mix.base_idx", mix.base_idx),
+                ("Mix.layer_idx", mix.layer_idx),
+            ],
+            _ => vec![],
+        }
+    }
+}
+
+/// Fixtures shared by the materials' tests.
+#[cfg(test)]
+mod test_util {
+    use std::f32::consts::TAU;
+
+    use glam::Vec3;
+    use rand::Rng;
+
+    use crate::geometry::{HitInfo, Tbn3};
+
+    /// A hit `t` along the ray at the origin of a surface facing +z.
+    pub fn hit(t: f32) -> HitInfo {
+        HitInfo {
+            t,
+            u: 0.5,
+            v: 0.5,
+            pos: Vec3::ZERO,
+            tbn: Tbn3::from_tn(Vec3::X, Vec3::Z),
+            uv_density: 1.0,
         }
     }
+
+    /// Estimates the integral of `f` over the hemisphere around +z.
+    pub fn integrate_over_hemisphere<R: Rng, F: FnMut(Vec3) -> f32>(
+        num_samples: usize,
+        rng: &mut R,
+        mut f: F,
+    ) -> f32 {
+        // Uniform directions on the hemisphere have a density of 1 / 2π.
+        let sum: f32 = (0..num_samples)
+            .map(|_| {
+                let z: f32 = rng.gen();
+                let (sin, cos) = (TAU * rng.gen::<f32>()).sin_cos();
+                let r = (1.0 - z * z).sqrt();
+                f(Vec3::new(r * cos, r * sin, z)) * TAU
+            })
+            .sum();
+
+        sum / num_samples as f32
+    }
 }
@@ -0,0 +1,291 @@
+use std::f32::consts::PI;
+
+use glam::Vec3;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+use crate::geometry::HitInfo;
+use crate::primitive::Ray3;
+use crate::texture::Texture;
+
+use super::microfacet::{fresnel_schlick, Frame, Ggx};
+use super::util::rand_unit_vector;
+use super::{Bsdf, Emission, Interaction};
+
+/// The part of the light that dielectrics (like plastic or paint) reflect
+/// straight back off their surface.
+const DIELECTRIC_F0: f32 = 0.04;
+
+/// A metallic-roughness material, like the ones of glTF files and most PBR
+/// tools. It's a blend of a diffuse base and GGX specular reflections. Metals
+/// tint their reflections with the base color and have no diffuse part.
+///
+/// `metallic` and `roughness` are multiplied by the blue and green channels of
+/// their textures, so a single glTF metallic-roughness texture can drive both.
+/// Their textures are read as linear data.
+#[derive(Serialize, Deserialize)]
+pub struct Principled {
+    pub base_color_idx: usize,
+    pub normal_map_idx: Option<usize>,
+    /// 0 for dielectrics and 1 for metals.
+    pub metallic: f32,
+    pub metallic_idx: Option<usize>,
+    /// 0 for mirror-like surfaces and 1 for completely rough ones.
+    pub roughness: f32,
+    pub roughness_idx: Option<usize>,
+    /// Makes the material glow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emission: Option<Emission>,
+}
+
+/// The material's parameters at a hit, seen from the direction `v` (in the
+/// frame around the shading normal).
+struct Surface {
+    frame: Frame,
+    v: Vec3,
+    base_color: Vec3,
+    metallic: f32,
+    ggx: Ggx,
+}
+
+impl Principled {
+    /// Picks either the diffuse or the specular part (weighted by how much
+    /// light each reflects) and samples it.
+    pub fn interact<R: Rng>(
+        &self,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        rng: &mut R,
+    ) -> Interaction {
+        let surface = self.surface(texture_cache, ray, hit);
+
+        let l = if rng.gen::<f32>() < surface.specular_probability() {
+            let h = surface.ggx.sample_visible_normal(surface.v, rng);
+            2.0 * surface.v.dot(h) * h - surface.v
+        } else {
+            (Vec3::Z + rand_unit_vector(rng)).normalize_or_zero()
+        };
+
+        let bsdf = surface.eval(l);
+
+        if bsdf.pdf > 0.0 {
+            Interaction::NonTerminal {
+                ray: Ray3::new(hit.pos, surface.frame.to_world(l)),
+                attenuation: bsdf.value / bsdf.pdf,
+            }
+        } else {
+            Interaction::Terminal { color: Vec3::ZERO }
+        }
+    }
+
+    pub fn bsdf(
+        &self,
+        texture_cache: &Cache<Texture>,
+        ray: &Ray3,
+        hit: &HitInfo,
+        dir: Vec3,
+    ) -> Bsdf {
+        let surface = self.surface(texture_cache, ray, hit);
+        surface.eval(surface.frame.to_local(dir.normalize()))
+    }
+
+    fn surface(&self, texture_cache: &Cache<Texture>, ray: &Ray3, hit: &HitInfo) -> Surface {
+        let coord = hit.tex_coord(ray);
+        let data = |idx: Option<usize>| {
+            idx.map(|idx| texture_cache[idx].data(texture_cache, coord))
+                .unwrap_or(Vec3::ONE)
+        };
+
+        let normal = self
+            .normal_map_idx
+            .map(|idx| texture_cache[idx].normal(texture_cache, coord, hit.tbn.matrix()))
+            .unwrap_or(hit.tbn.n);
+        let v = -ray.dir.normalize();
+        // Both sides of the surface look the same.
+        let frame = Frame::new(if normal.dot(v) < 0.0 { -normal } else { normal });
+
+        Surface {
+            v: frame.to_local(v),
+            frame,
+            base_color: texture_cache[self.base_color_idx].color(texture_cache, coord),
+            metallic: (self.metallic * data(self.metallic_idx).z).clamp(0.0, 1.0),
+            ggx: Ggx::new((self.roughness * data(self.roughness_idx).y).clamp(0.0, 1.0)),
+        }
+    }
+}
+
+impl Surface {
+    /// The color of light reflected straight back off the facets.
+    fn f0(&self) -> Vec3 {
+        Vec3::splat(DIELECTRIC_F0).lerp(self.base_color, self.metallic)
+    }
+
+    /// The part of the light which isn't reflected off the facets and gets
+    /// to the diffuse base.
+    fn diffuse_weight(&self) -> f32 {
+        let transmitted = 1.0 - fresnel_schlick(Vec3::splat(DIELECTRIC_F0), self.v.z.abs()).x;
+        (1.0 - self.metallic) * transmitted
+    }
+
+    fn specular_probability(&self) -> f32 {
+        let specular = fresnel_schlick(self.f0(), self.v.z.abs()).dot(Vec3::ONE) / 3.0;
+        let diffuse = self.diffuse_weight() * self.base_color.dot(Vec3::ONE) / 3.0;
+
+        if specular + diffuse > 0.0 {
+            specular / (specular + diffuse)
+        } else {
+            1.0
+        }
+    }
+
+    /// Evaluates the BSDF (with the cosine term) for light arriving from `l`
+    /// along with the probability density of `interact` picking `l`.
+    fn eval(&self, l: Vec3) -> Bsdf {
+        let v = self.v;
+        if v.z <= 0.0 || l.z <= 0.0 {
+            return Bsdf {
+                value: Vec3::ZERO,
+                pdf: 0.0,
+            };
+        }
+
+        let h = (v + l).normalize();
+        let fresnel = fresnel_schlick(self.f0(), v.dot(h));
+        let specular = fresnel * self.ggx.d(h) * self.ggx.g2(v, l) / (4.0 * v.z);
+        let diffuse = self.base_color * self.diffuse_weight() * l.z / PI;
+
+        // Reflecting `v` about `h` doubles the angles, so the density of
+        // reflected directions is a quarter of the one of the normals.
+        let specular_pdf = self.ggx.visible_normal_pdf(v, h) / (4.0 * v.dot(h));
+        let diffuse_pdf = l.z / PI;
+        let specular_probability = self.specular_probability();
+
+        Bsdf {
+            value: specular + diffuse,
+            pdf: specular_probability * specular_pdf + (1.0 - specular_probability) * diffuse_pdf,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use crate::material::test_util::{hit, integrate_over_hemisphere};
+    use crate::texture::Solid;
+
+    use super::*;
+
+    fn white_principled(metallic: f32, roughness: f32) -> (Cache<Texture>, Principled) {
+        let mut texture_cache = Cache::new();
+        let base_color_idx = texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+
+        let principled = Principled {
+            base_color_idx,
+            normal_map_idx: None,
+            metallic,
+            metallic_idx: None,
+            roughness,
+            roughness_idx: None,
+            emission: None,
+        };
+
+        (texture_cache, principled)
+    }
+
+    /// A ray that arrives at the hit at an angle (in radians) to the normal.
+    fn ray_at(angle: f32) -> Ray3 {
+        let (sin, cos) = angle.sin_cos();
+        Ray3::new(Vec3::new(sin, 0.0, cos), Vec3::new(-sin, 0.0, -cos))
+    }
+
+    /// The part of the light arriving along `ray` which is reflected, when
+    /// it's lit by a white furnace (light of 1 from every direction).
+    fn albedo(texture_cache: &Cache<Texture>, principled: &Principled, ray: &Ray3) -> f32 {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+        let num_samples = 100_000;
+
+        let sum: f32 = (0..num_samples)
+            .map(
+                |_| match principled.interact(texture_cache, ray, &hit(1.0), &mut rng) {
+                    Interaction::NonTerminal { attenuation, .. } => attenuation.x,
+                    Interaction::Terminal { .. } => 0.0,
+                },
+            )
+            .sum();
+
+        sum / num_samples as f32
+    }
+
+    #[test]
+    fn white_furnace_never_gains_energy() {
+        for metallic in [0.0, 0.5, 1.0] {
+            for roughness in [0.05, 0.3, 0.7, 1.0] {
+                let (texture_cache, principled) = white_principled(metallic, roughness);
+
+                for angle in [0.0, 0.8, 1.4] {
+                    let albedo = albedo(&texture_cache, &principled, &ray_at(angle));
+                    let label = format!(
+                        "metallic {} roughness {} angle {}",
+                        metallic, roughness, angle
+                    );
+
+                    assert!(albedo <= 1.01, "{} reflects {}", label, albedo);
+                    // Smooth white surfaces reflect (almost) everything. Rough
+                    // ones lose light to facets shadowing each other, since
+                    // light is only reflected once.
+                    if roughness <= 0.3 && angle <= 0.8 {
+                        assert!(albedo >= 0.98, "{} reflects {}", label, albedo);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn sampled_rays_are_weighted_like_the_bsdf() {
+        let (texture_cache, principled) = white_principled(0.3, 0.4);
+        let ray = ray_at(0.6);
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+
+        for _ in 0..100 {
+            if let Interaction::NonTerminal {
+                ray: scattered_ray,
+                attenuation,
+            } = principled.interact(&texture_cache, &ray, &hit(1.0), &mut rng)
+            {
+                let bsdf = principled.bsdf(&texture_cache, &ray, &hit(1.0), scattered_ray.dir);
+                assert!((bsdf.value / bsdf.pdf).abs_diff_eq(attenuation, 1e-3));
+            }
+        }
+    }
+
+    #[test]
+    fn importance_sampling_matches_uniform_sampling() {
+        for (metallic, roughness, angle) in [(0.0, 0.5, 0.8), (0.5, 0.3, 0.2), (1.0, 1.0, 1.2)] {
+            let (texture_cache, principled) = white_principled(metallic, roughness);
+            let ray = ray_at(angle);
+            let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+
+            let uniform_albedo = integrate_over_hemisphere(400_000, &mut rng, |dir| {
+                principled
+                    .bsdf(&texture_cache, &ray, &hit(1.0), dir)
+                    .value
+                    .x
+            });
+
+            let albedo = albedo(&texture_cache, &principled, &ray);
+            assert!(
+                (albedo - uniform_albedo).abs() < 0.02,
+                "{} != {} for metallic {} roughness {}",
+                albedo,
+                uniform_albedo,
+                metallic,
+                roughness
+            );
+        }
+    }
+}
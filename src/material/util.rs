@@ -1,9 +1,7 @@
 use glam::Vec3;
 use rand::Rng;
 
-pub(super) fn rand_pos_in_sphere(radius: f32) -> Vec3 {
-    let mut rng = rand::thread_rng();
-
+pub(super) fn rand_pos_in_sphere<R: Rng>(rng: &mut R, radius: f32) -> Vec3 {
     loop {
         let pos = 2.0 * Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::ONE;
 
@@ -12,3 +10,7 @@ pub(super) fn rand_pos_in_sphere(radius: f32) -> Vec3 {
         }
     }
 }
+
+pub(super) fn rand_unit_vector<R: Rng>(rng: &mut R) -> Vec3 {
+    rand_pos_in_sphere(rng, 1.0).normalize_or_zero()
+}
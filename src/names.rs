@@ -0,0 +1,174 @@
+//! Lets scene JSON files refer to cache entries by their names wherever an
+//! index is expected. Names are resolved to indices before a scene is
+//! deserialized and indices are replaced by names after it's serialized.
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::validation::{UnknownName, ValidationError};
+
+/// The parts of a scene which hold indices into its caches.
+const INDEXED_PARTS: [&str; 4] = ["sky_box", "texture_cache", "material_cache", "items"];
+
+/// Returns the cache that an index field points into.
+fn cache_of(field: &str) -> Option<&'static str> {
+    match field {
+        "geometry_idx" => Some("geometry_cache"),
+        "material_idx" | "base_idx" | "layer_idx" => Some("material_cache"),
+        "texture_idx" | "normal_map_idx" | "tex_idx" | "up_idx" | "dn_idx" | "lf_idx"
+        | "rt_idx" | "ft_idx" | "bk_idx" | "even_idx" | "odd_idx" | "base_color_idx"
+        | "metallic_idx" | "roughness_idx" | "mask_idx" => Some("texture_cache"),
+        _ => None,
+    }
+}
+
+/// Replaces the names in the index fields of a scene with their indices.
+/// Names which aren't in their cache are left as they are and reported.
+pub(crate) fn resolve_names(scene: &mut Value) -> Result<(), ValidationError> {
+    let idxs = cache_maps(scene, |name, idx| Some((name.clone(), idx.as_u64()?)));
+    let mut unknown_names = vec![];
+
+    for part in INDEXED_PARTS {
+        if let Some(value) = scene.get_mut(part) {
+            visit_idxs(value, part.to_owned(), &mut |path, cache, idx| {
+                if let Value::String(name) = idx {
+                    match idxs[cache].get(name) {
+                        Some(resolved) => *idx = Value::from(*resolved),
+                        None => unknown_names.push(UnknownName {
+                            path: path.to_owned(),
+                            name: name.clone(),
+                            cache,
+                        }),
+                    }
+                }
+            });
+        }
+    }
+
+    if unknown_names.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError {
+            unknown_names,
+            ..Default::default()
+        })
+    }
+}
+
+/// Replaces the indices in the index fields of a scene with their names.
+pub(crate) fn use_names(scene: &mut Value) {
+    let names = cache_maps(scene, |name, idx| Some((idx.as_u64()?, name.clone())));
+
+    for part in INDEXED_PARTS {
+        if let Some(value) = scene.get_mut(part) {
+            visit_idxs(value, part.to_owned(), &mut |_, cache, idx| {
+                if let Some(name) = idx.as_u64().and_then(|idx| names[cache].get(&idx)) {
+                    *idx = Value::from(name.as_str());
+                }
+            });
+        }
+    }
+}
+
+/// Maps the name to index map of every cache with `entry`.
+fn cache_maps<K, V, F>(scene: &Value, entry: F) -> HashMap<&'static str, HashMap<K, V>>
+where
+    K: Eq + std::hash::Hash,
+    F: Fn(&String, &Value) -> Option<(K, V)>,
+{
+    ["texture_cache", "geometry_cache", "material_cache"]
+        .iter()
+        .map(|&cache| {
+            let map = match scene.pointer(&format!("/{}/idxs", cache)) {
+                Some(Value::Object(idxs)) => idxs
+                    .iter()
+                    .filter_map(|(name, idx)| entry(name, idx))
+                    .collect(),
+                _ => HashMap::new(),
+            };
+            (cache, map)
+        })
+        .collect()
+}
+
+/// Calls `f` with the JSON path, the cache and the value of every index field
+/// in `value`. The name to index maps of caches are skipped since their keys
+/// are names, not fields.
+fn visit_idxs<F>(value: &mut Value, path: String, f: &mut F)
+where
+    F: FnMut(&str, &'static str, &mut Value),
+{
+    match value {
+        Value::Array(values) => {
+            for (i, value) in values.iter_mut().enumerate() {
+                visit_idxs(value, format!("{}[{}]", path, i), f);
+            }
+        }
+        Value::Object(fields) => {
+            for (field, value) in fields.iter_mut().filter(|(field, _)| *field != "idxs") {
+                let path = format!("{}.{}", path, field);
+                match cache_of(field) {
+                    Some(cache) => f(&path, cache, value),
+                    None => visit_idxs(value, path, f),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn scene(sky_idx: Value, texture_idx: Value, material_idx: Value) -> Value {
+        json!({
+            "sky_box": {"Equirectangular": {"tex_idx": sky_idx}},
+            "texture_cache": {"idxs": {"sky": 0, "red": 1}, "items": []},
+            "material_cache": {
+                "idxs": {"texture_idx": 0},
+                "items": [{"Lambertian": {"texture_idx": texture_idx, "normal_map_idx": null}}]
+            },
+            "items": [{"geometry_idx": 3, "material_idx": material_idx}]
+        })
+    }
+
+    #[test]
+    fn names_are_resolved_to_indices() {
+        let mut value = scene(json!("sky"), json!("red"), json!("texture_idx"));
+        resolve_names(&mut value).unwrap();
+
+        assert_eq!(scene(json!(0), json!(1), json!(0)), value);
+    }
+
+    #[test]
+    fn indices_are_replaced_by_names() {
+        let mut value = scene(json!(0), json!(1), json!(0));
+        use_names(&mut value);
+
+        // The geometry has no name, so its index is kept.
+        assert_eq!(
+            scene(json!("sky"), json!("red"), json!("texture_idx")),
+            value
+        );
+        assert_eq!(json!(3), value["items"][0]["geometry_idx"]);
+    }
+
+    #[test]
+    fn unknown_name_is_reported_with_its_path() {
+        let mut value = scene(json!("sky"), json!("blue"), json!(0));
+        let err = resolve_names(&mut value).unwrap_err();
+
+        assert_eq!(
+            vec![UnknownName {
+                path: "material_cache.items[0].Lambertian.texture_idx".to_owned(),
+                name: "blue".to_owned(),
+                cache: "texture_cache",
+            }],
+            err.unknown_names
+        );
+    }
+}
@@ -1,5 +1,5 @@
 mod ray3;
 mod vec3;
 
-pub use ray3::Ray3;
+pub use ray3::{Ray3, RayCone};
 pub use vec3::Vec3Utils;
@@ -1,13 +1,49 @@
 use glam::Vec3;
 
+use crate::spectrum::Wavelengths;
+
 pub struct Ray3 {
     pub pos: Vec3,
     pub dir: Vec3,
+    /// The moment at which the ray is cast. Moving geometry is hit where it
+    /// is at this time.
+    pub time: f32,
+    pub cone: RayCone,
+    /// The wavelengths of light the ray carries, when rendering spectrally.
+    pub wavelengths: Option<Wavelengths>,
+}
+
+/// A cone around a ray which approximates the area the ray stands for, like
+/// the pixel a camera ray goes through. Textures are filtered over the part
+/// of the surface inside the cone.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RayCone {
+    /// The width of the cone at the ray's origin.
+    pub width: f32,
+    /// How much the width grows per unit of distance along the ray.
+    pub spread: f32,
+}
+
+impl RayCone {
+    pub fn width_at(&self, distance: f32) -> f32 {
+        self.width + self.spread * distance
+    }
 }
 
 impl Ray3 {
+    /// Creates a ray cast at time 0. It's infinitely thin.
     pub fn new(pos: Vec3, dir: Vec3) -> Self {
-        Ray3 { pos, dir }
+        Self::at_time(pos, dir, 0.0)
+    }
+
+    pub fn at_time(pos: Vec3, dir: Vec3, time: f32) -> Self {
+        Ray3 {
+            pos,
+            dir,
+            time,
+            cone: RayCone::default(),
+            wavelengths: None,
+        }
     }
 
     pub fn point_at_param(&self, t: f32) -> Vec3 {
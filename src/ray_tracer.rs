@@ -1,15 +1,23 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
 use std::path::Path;
 
 use clap::Args;
 use glam::Vec3;
-use image::{ColorType, ImageResult};
+use image::codecs::hdr::HdrEncoder;
+use image::{ColorType, ImageBuffer, ImageResult, Rgb};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
 use rayon::prelude::*;
 
-use crate::material::Interaction;
+use crate::checkpoint::{hash_scene, Checkpoint};
+use crate::geometry::HitInfo;
+use crate::material::{Interaction, Material};
 use crate::primitive::Ray3;
-use crate::scene::{HitInfoAndMaterial, Scene};
+use crate::scene::Scene;
+use crate::spectrum::Wavelengths;
+use crate::texture::ColorSpace;
 use crate::tone_mapper::ToneMapper;
 
 #[derive(Args)]
@@ -36,35 +44,169 @@ pub struct RayTracer {
     /// HDR inputs into very different outputs.
     #[clap(long = "tone-mapper", arg_enum, default_value = "clamp")]
     pub tone_mapper: ToneMapper,
+
+    /// The number of samples taken per pixel in every pass of a progressive
+    /// render. The render is saved after every pass.
+    #[clap(long = "pass-samples", default_value_t = 16)]
+    pub samples_per_pass: u16,
+
+    /// The seed of the RNGs used while rendering. The same seed will result in
+    /// the same image, whether it's rendered at once or in passes. If you
+    /// don't provide a seed, a random one is used.
+    #[clap(long)]
+    pub seed: Option<u64>,
+
+    /// Trace rays at random wavelengths of visible light instead of in RGB,
+    /// so that glass with dispersion splits white light into its colors.
+    /// Scenes without dispersion look the same, but with more noise.
+    #[clap(long)]
+    pub spectral: bool,
 }
 
 impl RayTracer {
+    /// Renders the scene and saves it. The extension of the path decides the
+    /// image format. See `save_image` for details.
     pub fn render_to_file<P: AsRef<Path>>(&self, scene: &Scene, path: P) -> ImageResult<()> {
-        let pixels = self.color_scene(&scene);
-        image::save_buffer(
-            path,
-            &pixels,
-            self.canvas_wd,
-            self.canvas_ht,
-            ColorType::Rgb8,
-        )
+        let bar = progress_bar(self.canvas_ht as u64);
+        let sums = self.sum_samples(scene, 0, self.num_samples as u32, &bar);
+        self.save_image(&sums, self.num_samples as u32, path)
+    }
+
+    /// Renders the scene in passes of `samples_per_pass` samples per pixel.
+    /// The samples are saved to the checkpoint file after every pass, so a
+    /// render which is stopped can be resumed later. When resuming, only the
+    /// samples missing from the checkpoint are taken.
+    pub fn render_progressively<P: AsRef<Path>, Q: AsRef<Path>>(
+        &self,
+        scene: &Scene,
+        path: P,
+        checkpoint_path: Q,
+        resume: bool,
+    ) -> ImageResult<()> {
+        let scene_hash = hash_scene(scene, &[self.max_reflections as u64, self.spectral as u64]);
+
+        let mut checkpoint = if resume {
+            let checkpoint = Checkpoint::load(&checkpoint_path)?;
+            if checkpoint.scene_hash != scene_hash
+                || checkpoint.canvas_wd != self.canvas_wd
+                || checkpoint.canvas_ht != self.canvas_ht
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "The checkpoint belongs to a different scene or canvas size",
+                )
+                .into());
+            }
+            checkpoint
+        } else {
+            Checkpoint::new(scene_hash, self.canvas_wd, self.canvas_ht)
+        };
+
+        let num_samples = self.num_samples as u32;
+        let samples_per_pass = self.samples_per_pass.max(1) as u32;
+        let num_passes = num_samples
+            .saturating_sub(checkpoint.num_samples)
+            .div_ceil(samples_per_pass);
+        let bar = progress_bar(self.canvas_ht as u64 * num_passes as u64);
+
+        while checkpoint.num_samples < num_samples {
+            let pass_samples = samples_per_pass.min(num_samples - checkpoint.num_samples);
+            let sums = self.sum_samples(scene, checkpoint.num_samples, pass_samples, &bar);
+            checkpoint.add(&sums, pass_samples);
+            checkpoint.save(&checkpoint_path)?;
+        }
+        bar.finish();
+
+        self.save_image(&checkpoint.sums, checkpoint.num_samples, path)
     }
 
     pub fn color_scene(&self, scene: &Scene) -> Vec<u8> {
-        let bar = ProgressBar::new(self.canvas_ht as u64).with_style(
-            ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {percent}%")
-                .progress_chars("#>-"),
-        );
-        bar.set_draw_delta(4);
+        let bar = progress_bar(self.canvas_ht as u64);
+        let sums = self.sum_samples(scene, 0, self.num_samples as u32, &bar);
+        self.to_rgb8(&sums, self.num_samples as u32)
+    }
+
+    /// Saves the average of the samples of every pixel. OpenEXR (.exr) and
+    /// Radiance HDR (.hdr) images get the linear radiance as is, so they can be
+    /// tone mapped elsewhere. Other formats get tone mapped and sRGB encoded
+    /// 8 bit colors.
+    fn save_image<P: AsRef<Path>>(
+        &self,
+        sums: &[Vec3],
+        num_samples: u32,
+        path: P,
+    ) -> ImageResult<()> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        let radiance = sums.iter().map(|sum| *sum / num_samples as f32);
+
+        match extension.as_deref() {
+            Some("exr") => {
+                let floats = radiance.flat_map(|color| color.to_array()).collect();
+                ImageBuffer::<Rgb<f32>, Vec<f32>>::from_raw(self.canvas_wd, self.canvas_ht, floats)
+                    .expect("Pixel count doesn't match the canvas size")
+                    .save(path)
+            }
+            Some("hdr") => {
+                let pixels: Vec<Rgb<f32>> = radiance.map(|color| Rgb(color.to_array())).collect();
+                HdrEncoder::new(BufWriter::new(File::create(path)?)).encode(
+                    &pixels,
+                    self.canvas_wd as usize,
+                    self.canvas_ht as usize,
+                )
+            }
+            _ => image::save_buffer(
+                path,
+                &self.to_rgb8(sums, num_samples),
+                self.canvas_wd,
+                self.canvas_ht,
+                ColorType::Rgb8,
+            ),
+        }
+    }
+
+    /// Returns the sum of `num_samples` samples for every pixel, row by row
+    /// from the top. `first_sample` is the number of samples taken earlier,
+    /// so that later passes don't repeat the samples of earlier ones.
+    fn sum_samples(
+        &self,
+        scene: &Scene,
+        first_sample: u32,
+        num_samples: u32,
+        bar: &ProgressBar,
+    ) -> Vec<Vec3> {
+        let seed = self.seed.unwrap_or_else(|| rand::thread_rng().gen());
 
         (0..self.canvas_ht)
             .into_par_iter()
             .rev()
-            .progress_with(bar)
+            .progress_with(bar.clone())
             .flat_map_iter(|j| (0..self.canvas_wd).map(move |i| (i, j)))
-            .flat_map_iter(|(i, j)| {
-                let color = self.tone_mapper.map(self.color_pixel(&scene, i, j));
+            .map(|(i, j)| {
+                let pixel_idx = j as u64 * self.canvas_wd as u64 + i as u64;
+
+                (first_sample..first_sample + num_samples).fold(Vec3::ZERO, |sum, sample_idx| {
+                    // Every sample gets its own RNG, so the samples don't
+                    // depend on the order in which threads pick up pixels
+                    // or on how the samples are split into passes.
+                    let mut rng = Xoshiro256PlusPlus::seed_from_u64(
+                        seed ^ (pixel_idx << 32 | sample_idx as u64),
+                    );
+                    sum + self.color_sample(scene, i, j, &mut rng)
+                })
+            })
+            .collect()
+    }
+
+    /// Averages the sums of samples and maps them to 8 bit colors.
+    fn to_rgb8(&self, sums: &[Vec3], num_samples: u32) -> Vec<u8> {
+        sums.par_iter()
+            .flat_map_iter(|sum| {
+                let color = self.tone_mapper.map(*sum / num_samples as f32);
+                let color = ColorSpace::Srgb.encode(color);
 
                 let r = (255.99 * color.x) as u8;
                 let g = (255.99 * color.y) as u8;
@@ -75,36 +217,467 @@ impl RayTracer {
             .collect()
     }
 
-    fn color_pixel(&self, scene: &Scene, i: u32, j: u32) -> Vec3 {
-        let mut color = Vec3::ZERO;
-        let mut rng = rand::thread_rng();
+    /// Takes a single sample of the pixel at column `i` and row `j`.
+    fn color_sample<R: Rng>(&self, scene: &Scene, i: u32, j: u32, rng: &mut R) -> Vec3 {
+        let u = (i as f32 + rng.gen::<f32>()) / self.canvas_wd as f32;
+        let v = (j as f32 + rng.gen::<f32>()) / self.canvas_ht as f32;
 
-        for _ in 0..self.num_samples {
-            let u = (i as f32 + rng.gen::<f32>()) / self.canvas_wd as f32;
-            let v = (j as f32 + rng.gen::<f32>()) / self.canvas_ht as f32;
+        let mut ray = scene.camera.get_ray(u, v, rng);
+        ray.cone = scene.camera.ray_cone(self.canvas_ht);
 
-            let ray = scene.camera.get_ray(u, v);
-            color = color + self.color_ray(&ray, scene, 0);
+        if self.spectral {
+            let wavelengths = Wavelengths::sample(rng);
+            ray.wavelengths = Some(wavelengths);
+            wavelengths.weight() * self.color_ray(&ray, scene, 0, None, rng)
+        } else {
+            self.color_ray(&ray, scene, 0, None, rng)
         }
-
-        let color = color / self.num_samples as f32;
-        Vec3::new(color.x.sqrt(), color.y.sqrt(), color.z.sqrt())
     }
 
-    fn color_ray(&self, ray: &Ray3, scene: &Scene, depth: u8) -> Vec3 {
+    /// Traces a path through the scene. Lights are found both by sampling
+    /// them directly at every bounce but the last and by bouncing into them.
+    /// The two are combined using multiple importance sampling.
+    ///
+    /// `bsdf_pdf` is the probability density with which the ray was scattered
+    /// in its direction. It's `None` for camera rays and rays scattered in a
+    /// discrete direction, which can't find lights any other way.
+    fn color_ray<R: Rng>(
+        &self,
+        ray: &Ray3,
+        scene: &Scene,
+        depth: u8,
+        bsdf_pdf: Option<f32>,
+        rng: &mut R,
+    ) -> Vec3 {
         if depth >= self.max_reflections {
             return Vec3::ZERO;
         }
 
-        if let Some(HitInfoAndMaterial(hit_info, material)) = scene.hit(ray, 0.001, f32::MAX) {
-            match material.interact(&scene.texture_cache, ray, &hit_info) {
-                Interaction::NonTerminal { ray, attenuation } => {
-                    self.color_ray(&ray, scene, depth + 1) * attenuation
+        let (item_idx, hit_info) = match scene.hit_item(ray, 0.001, f32::MAX) {
+            Some(hit) => hit,
+            None => return scene.sky_box.color(&scene.texture_cache, ray.dir),
+        };
+        let material = scene.material_of(item_idx);
+        let emitted = material.emitted(&scene.material_cache, &scene.texture_cache, ray, &hit_info);
+
+        let interaction = material.interact(
+            &scene.material_cache,
+            &scene.texture_cache,
+            ray,
+            &hit_info,
+            rng,
+        );
+
+        // The light sample doesn't depend on the BSDF sample. Rough surfaces
+        // can scatter below themselves and still be lit directly. At the last
+        // bounce the BSDF ray can't find lights, so the light sample's MIS
+        // weight would leave out some of their light. It's skipped instead,
+        // like any light more than `max_reflections` bounces away.
+        let direct = if depth + 1 < self.max_reflections {
+            self.color_light_sample(ray, &hit_info, material, scene, rng)
+        } else {
+            Vec3::ZERO
+        };
+
+        let scattered = match interaction {
+            Interaction::NonTerminal {
+                ray: scattered_ray,
+                attenuation,
+            } => {
+                let bsdf_pdf = material
+                    .bsdf(
+                        &scene.material_cache,
+                        &scene.texture_cache,
+                        ray,
+                        &hit_info,
+                        scattered_ray.dir,
+                    )
+                    .map(|bsdf| bsdf.pdf);
+
+                self.color_ray(&scattered_ray, scene, depth + 1, bsdf_pdf, rng) * attenuation
+            }
+            Interaction::Terminal { color } => match bsdf_pdf {
+                Some(bsdf_pdf) => {
+                    color * power_heuristic(bsdf_pdf, scene.light_pdf(item_idx, ray, &hit_info))
                 }
-                Interaction::Terminal { color } => color,
+                None => color,
+            },
+        };
+
+        emitted + direct + scattered
+    }
+
+    /// Returns the light arriving at the hit from a point picked on one of the
+    /// scene's lights, weighted for multiple importance sampling.
+    fn color_light_sample<R: Rng>(
+        &self,
+        ray: &Ray3,
+        hit_info: &HitInfo,
+        material: &Material,
+        scene: &Scene,
+        rng: &mut R,
+    ) -> Vec3 {
+        let light_sample = match scene.sample_light(hit_info.pos, rng) {
+            Some(light_sample) => light_sample,
+            None => return Vec3::ZERO,
+        };
+
+        let bsdf = match material.bsdf(
+            &scene.material_cache,
+            &scene.texture_cache,
+            ray,
+            hit_info,
+            light_sample.dir,
+        ) {
+            Some(bsdf) if bsdf.pdf > 0.0 => bsdf,
+            _ => return Vec3::ZERO,
+        };
+
+        // The light is visible if the shadow ray hits it at the picked point.
+        let shadow_ray = Ray3::at_time(hit_info.pos, light_sample.dir, ray.time);
+        let light_hit = match scene.hit_item(&shadow_ray, 0.001, 1.001) {
+            Some((item_idx, light_hit))
+                if item_idx == light_sample.item_idx && light_hit.t > 0.999 =>
+            {
+                light_hit
             }
-        } else {
-            scene.sky_box.color(&scene.texture_cache, ray.dir)
+            _ => return Vec3::ZERO,
+        };
+
+        match scene.material_of(light_sample.item_idx).interact(
+            &scene.material_cache,
+            &scene.texture_cache,
+            &shadow_ray,
+            &light_hit,
+            rng,
+        ) {
+            Interaction::Terminal { color } => {
+                color * bsdf.value * power_heuristic(light_sample.pdf, bsdf.pdf) / light_sample.pdf
+            }
+            Interaction::NonTerminal { .. } => Vec3::ZERO,
+        }
+    }
+}
+
+fn progress_bar(len: u64) -> ProgressBar {
+    let bar = ProgressBar::new(len).with_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] [{bar:40.cyan/blue}] {percent}%")
+            .progress_chars("#>-"),
+    );
+    bar.set_draw_delta(4);
+    bar
+}
+
+/// The weight of a sample taken with probability density `pdf` when another
+/// strategy could've taken it with probability density `other_pdf`.
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    if other_pdf <= 0.0 {
+        1.0
+    } else {
+        pdf.powi(2) / (pdf.powi(2) + other_pdf.powi(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::cache::Cache;
+    use crate::camera::{Camera, CameraInitOptions, Projection};
+    use crate::geometry::{Geometry, Sphere};
+    use crate::item::Item;
+    use crate::material::{Dielectric, Lambertian, Light, Metal};
+    use crate::scene::SceneDescription;
+    use crate::sky_box::{Cubemap, SkyBox};
+    use crate::texture::{Solid, Texture};
+
+    use super::*;
+
+    fn ray_tracer() -> RayTracer {
+        RayTracer {
+            canvas_wd: 2,
+            canvas_ht: 1,
+            num_samples: 4,
+            max_reflections: 4,
+            tone_mapper: ToneMapper::Clamp,
+            samples_per_pass: 4,
+            seed: Some(7),
+            spectral: false,
+        }
+    }
+
+    fn camera() -> Camera {
+        CameraInitOptions {
+            pos: Vec3::new(0.0, 0.0, 4.0),
+            look_at: Vec3::ZERO,
+            vup: Vec3::Y,
+            vt_fov: 40.0,
+            aspect: 2.0,
+            projection: Projection::Perspective,
+            aperture: 0.0,
+            focus_distance: None,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+        .into()
+    }
+
+    /// A small scene with every kind of material and a light.
+    fn scene() -> Scene {
+        let mut texture_cache = Cache::new();
+        let white = texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+
+        let mut geometry_cache = Cache::new();
+        let mut material_cache = Cache::new();
+        let mut items = vec![];
+
+        let materials = vec![
+            Material::Dielectric(Dielectric {
+                ref_idx: 1.5,
+                normal_map_idx: None,
+                roughness: 0.0,
+                absorption: None,
+                dispersion: None,
+                emission: None,
+            }),
+            Material::Lambertian(Lambertian {
+                texture_idx: white,
+                normal_map_idx: None,
+                emission: None,
+            }),
+            Material::Light(Light {
+                texture_idx: white,
+                brightness: Vec3::splat(4.0),
+                one_sided: false,
+            }),
+            Material::Metal(Metal {
+                texture_idx: white,
+                normal_map_idx: None,
+                fuzz: 0.3,
+                emission: None,
+            }),
+        ];
+
+        for (idx, material) in materials.into_iter().enumerate() {
+            let name = idx.to_string();
+            let sphere = Sphere {
+                center: Vec3::new(idx as f32 - 1.5, 0.0, 0.0),
+                radius: 0.4,
+            };
+            items.push(Item {
+                geometry_idx: geometry_cache.add(&name, Geometry::Sphere(sphere)),
+                material_idx: material_cache.add(&name, material),
+                transform: None,
+            });
+        }
+
+        SceneDescription {
+            sky_box: SkyBox::Cubemap(Cubemap::all(white)),
+            camera: camera(),
+            texture_cache,
+            geometry_cache,
+            material_cache,
+            items,
+            models: vec![],
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn renders_with_the_same_seed_match() {
+        let ray_tracer = RayTracer {
+            canvas_wd: 16,
+            canvas_ht: 8,
+            ..ray_tracer()
+        };
+        let scene = scene();
+        let bar = ProgressBar::hidden();
+
+        // The scene is mostly brighter than white, so the sums are compared
+        // rather than the clamped 8 bit colors.
+        let sums = ray_tracer.sum_samples(&scene, 0, 4, &bar);
+        assert_eq!(sums, ray_tracer.sum_samples(&scene, 0, 4, &bar));
+
+        let other_seed = RayTracer {
+            seed: Some(8),
+            ..ray_tracer
+        };
+        assert_ne!(sums, other_seed.sum_samples(&scene, 0, 4, &bar));
+    }
+
+    #[test]
+    fn splitting_samples_into_passes_keeps_the_image() {
+        let ray_tracer = RayTracer {
+            canvas_wd: 8,
+            canvas_ht: 4,
+            ..ray_tracer()
+        };
+        let scene = scene();
+        let bar = ProgressBar::hidden();
+
+        let one_pass = ray_tracer.sum_samples(&scene, 0, 4, &bar);
+        let first_pass = ray_tracer.sum_samples(&scene, 0, 1, &bar);
+        let second_pass = ray_tracer.sum_samples(&scene, 1, 3, &bar);
+
+        for (sum, (first, second)) in one_pass.iter().zip(first_pass.iter().zip(&second_pass)) {
+            assert!(
+                sum.abs_diff_eq(*first + *second, 1e-4),
+                "{} != {} + {}",
+                sum,
+                first,
+                second
+            );
+        }
+    }
+
+    #[test]
+    fn float_images_keep_linear_radiance() {
+        // These are exact in both formats. Radiance HDR shares an exponent
+        // between the channels of a pixel.
+        let sums = [Vec3::new(2.0, 4.0, 8.0), Vec3::new(32.0, 16.0, 16.0)];
+
+        let path = std::env::temp_dir().join("raytracer_linear.exr");
+        ray_tracer().save_image(&sums, 4, &path).unwrap();
+        let image = image::open(&path).unwrap().into_rgb32f();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(&Rgb([0.5, 1.0, 2.0]), image.get_pixel(0, 0));
+        assert_eq!(&Rgb([8.0, 4.0, 4.0]), image.get_pixel(1, 0));
+
+        // image::open converts Radiance HDR images to 8 bit colors.
+        let path = std::env::temp_dir().join("raytracer_linear.hdr");
+        ray_tracer().save_image(&sums, 4, &path).unwrap();
+        let reader = std::io::BufReader::new(File::open(&path).unwrap());
+        let pixels = image::codecs::hdr::HdrDecoder::new(reader)
+            .unwrap()
+            .read_image_hdr()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(vec![Rgb([0.5, 1.0, 2.0]), Rgb([8.0, 4.0, 4.0])], pixels);
+    }
+
+    #[test]
+    fn rough_metal_matches_light_sampling_reference() {
+        let mut texture_cache = Cache::new();
+        let white = texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+        let black = texture_cache.add("black", Texture::Solid(Solid { color: Vec3::ZERO }));
+
+        let mut geometry_cache = Cache::new();
+        let mut material_cache = Cache::new();
+        let spheres = vec![
+            (
+                Sphere {
+                    center: Vec3::ZERO,
+                    radius: 1.0,
+                },
+                Material::Metal(Metal {
+                    texture_idx: white,
+                    normal_map_idx: None,
+                    fuzz: 0.8,
+                    emission: None,
+                }),
+            ),
+            (
+                Sphere {
+                    center: Vec3::new(3.0, 2.5, 0.0),
+                    radius: 0.5,
+                },
+                Material::Light(Light {
+                    texture_idx: white,
+                    brightness: Vec3::ONE,
+                    one_sided: false,
+                }),
+            ),
+        ];
+        let items = spheres
+            .into_iter()
+            .enumerate()
+            .map(|(idx, (sphere, material))| {
+                let name = idx.to_string();
+                Item {
+                    geometry_idx: geometry_cache.add(&name, Geometry::Sphere(sphere)),
+                    material_idx: material_cache.add(&name, material),
+                    transform: None,
+                }
+            })
+            .collect();
+
+        let scene: Scene = SceneDescription {
+            sky_box: SkyBox::Cubemap(Cubemap::all(black)),
+            camera: camera(),
+            texture_cache,
+            geometry_cache,
+            material_cache,
+            items,
+            models: vec![],
+        }
+        .try_into()
+        .unwrap();
+
+        // A grazing ray, so that many fuzzed reflections go below the surface.
+        let ray = Ray3::new(Vec3::new(-3.0, 1.6, 0.0), Vec3::new(3.0, -0.6, 0.0));
+        let (item_idx, hit_info) = scene.hit_item(&ray, 0.001, f32::MAX).unwrap();
+        let material = scene.material_of(item_idx);
+
+        let num_samples = 200_000;
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(1);
+
+        // Only the light sample is used for the reference. The light has a
+        // radiance of 1 but can hide the picked point behind itself.
+        let mut reference = Vec3::ZERO;
+        for _ in 0..num_samples {
+            let light_sample = scene.sample_light(hit_info.pos, &mut rng).unwrap();
+            let shadow_ray = Ray3::new(hit_info.pos, light_sample.dir);
+            match scene.hit_item(&shadow_ray, 0.001, 1.001) {
+                Some((_, light_hit)) if light_hit.t > 0.999 => {}
+                _ => continue,
+            }
+            let bsdf = material
+                .bsdf(
+                    &scene.material_cache,
+                    &scene.texture_cache,
+                    &ray,
+                    &hit_info,
+                    light_sample.dir,
+                )
+                .unwrap();
+            reference += bsdf.value / light_sample.pdf;
+        }
+        reference /= num_samples as f32;
+
+        // Two bounces reach the light from the metal and nothing further.
+        let ray_tracer = RayTracer {
+            max_reflections: 2,
+            ..ray_tracer()
+        };
+        let mut color = Vec3::ZERO;
+        for _ in 0..num_samples {
+            color += ray_tracer.color_ray(&ray, &scene, 0, None, &mut rng);
+        }
+        color /= num_samples as f32;
+
+        assert!(reference.x > 0.0);
+        assert!(
+            (color - reference).abs().max_element() < 0.02 * reference.x,
+            "{} != {}",
+            color,
+            reference
+        );
+
+        // With a single bounce the light is out of reach for both strategies.
+        // A partly weighted light sample mustn't leak through.
+        let ray_tracer = RayTracer {
+            max_reflections: 1,
+            ..ray_tracer
+        };
+        for _ in 0..100 {
+            assert_eq!(
+                Vec3::ZERO,
+                ray_tracer.color_ray(&ray, &scene, 0, None, &mut rng)
+            );
         }
     }
 }
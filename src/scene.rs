@@ -1,19 +1,54 @@
-use std::fs::File;
-use std::io::{self, Read};
-use std::path::Path;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use serde::{Deserialize, Serialize};
+use glam::Vec3;
+use rand::Rng;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
 
+use crate::assets::{self, AssetPaths};
+use crate::bvh::{Aabb, Bvh};
 use crate::cache::Cache;
 use crate::camera::Camera;
-use crate::geometry::{Geometry, HitInfo};
+use crate::error::{self, Error};
+use crate::geometry::{self, Geometry, HitInfo, Mesh};
+use crate::import::{self, Model};
 use crate::item::Item;
 use crate::material::Material;
+use crate::names;
 use crate::primitive::Ray3;
 use crate::sky_box::SkyBox;
-use crate::texture::Texture;
+use crate::texture::{Filter, MipMap, Texture};
+use crate::validation::{self, ValidationError};
 
+/// Everything needed to describe a scene. This is what scene JSON files hold.
+///
+/// Cache entries can be referred to by their names wherever an index is
+/// expected. Indices are written as names.
 #[derive(Serialize, Deserialize)]
+#[serde(remote = "Self")]
+pub struct SceneDescription {
+    pub sky_box: SkyBox,
+    pub camera: Camera,
+    pub texture_cache: Cache<Texture>,
+    pub geometry_cache: Cache<Geometry>,
+    pub material_cache: Cache<Material>,
+    pub items: Vec<Item>,
+    /// Asset files whose contents are added to the scene when it's loaded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<Model>,
+}
+
+/// A scene which is ready to be ray traced. It's created from a
+/// `SceneDescription` by importing its models and building a BVH over its
+/// items.
+#[derive(Serialize)]
+#[serde(remote = "Self")]
 pub struct Scene {
     pub sky_box: SkyBox,
     pub camera: Camera,
@@ -21,46 +56,563 @@ pub struct Scene {
     pub geometry_cache: Cache<Geometry>,
     pub material_cache: Cache<Material>,
     pub items: Vec<Item>,
+    #[serde(skip_serializing)]
+    bvh: Bvh,
+    /// Indices of the items which emit light and can be sampled directly.
+    #[serde(skip_serializing)]
+    lights: Vec<usize>,
+}
+
+/// The memory used by the images of a scene.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ImageMemory {
+    /// The number of image textures.
+    pub textures: usize,
+    /// The number of decoded images. It's less than `textures` when textures
+    /// share images.
+    pub images: usize,
+    pub bytes: usize,
 }
 
 pub struct HitInfoAndMaterial<'a>(pub HitInfo, pub &'a Material);
 
+/// A point picked on one of the scene's lights.
+pub struct LightSample {
+    pub item_idx: usize,
+    /// Goes from the point being lit to the picked point. So the light is hit
+    /// at `t` = 1 along it.
+    pub dir: Vec3,
+    /// The probability density (per unit solid angle) of picking the point.
+    pub pdf: f32,
+}
+
+impl SceneDescription {
+    /// Decodes the image textures which weren't decoded yet, like the ones
+    /// read from JSON. Their paths are replaced by the files that were found.
+    ///
+    /// Every file is decoded once, however many textures use it and whatever
+    /// paths they use for it.
+    pub fn load_images(&mut self, assets: &AssetPaths) -> error::Result<()> {
+        let mut decoded: HashMap<PathBuf, Arc<MipMap>> = self
+            .texture_cache
+            .iter()
+            .filter_map(|texture| match texture {
+                Texture::Image(image) => Some((
+                    Path::new(&image.path).canonicalize().ok()?,
+                    image.mipmap()?.clone(),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        for texture in self.texture_cache.iter_mut() {
+            if let Texture::Image(image) = texture {
+                if image.is_loaded() {
+                    continue;
+                }
+
+                let path = assets.resolve(&image.path);
+                let canonical_path = path.canonicalize().unwrap_or_else(|_| path.clone());
+
+                let mipmap = match decoded.get(&canonical_path) {
+                    Some(mipmap) => mipmap.clone(),
+                    None => {
+                        let mipmap = Arc::new(MipMap::load(&path)?);
+                        decoded.insert(canonical_path, mipmap.clone());
+                        mipmap
+                    }
+                };
+
+                // They'd be made while rendering otherwise. Normal maps read
+                // in another color space still make theirs then.
+                if image.sampler.filter == Filter::Trilinear {
+                    mipmap.make_levels(image.color_space.unwrap_or_else(|| mipmap.color_space()));
+                }
+
+                image.path = path
+                    .to_str()
+                    .ok_or_else(|| {
+                        Error::io(
+                            &path,
+                            io::Error::new(io::ErrorKind::InvalidInput, "Non UTF-8 path"),
+                        )
+                    })?
+                    .to_owned();
+                image.set_mipmap(mipmap);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads the meshes which weren't loaded yet, like the ones read from
+    /// JSON. Their paths are replaced by the files that were found.
+    ///
+    /// Every OBJ file is parsed once, however many meshes use it.
+    pub fn load_meshes(&mut self, assets: &AssetPaths) -> error::Result<()> {
+        let mut parsed = HashMap::new();
+
+        for geometry in self.geometry_cache.iter_mut() {
+            if let Geometry::Mesh(mesh) = geometry {
+                let path = match &mesh.path {
+                    Some(path) if !mesh.is_loaded() => assets.resolve(path),
+                    _ => continue,
+                };
+                let canonical_path = path.canonicalize().unwrap_or_else(|_| path.clone());
+
+                let (models, materials) = match parsed.entry(canonical_path) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(geometry::load_obj(&path)?),
+                };
+
+                let loaded = Mesh::from_obj(&path, mesh.material.as_deref(), models, materials)?;
+                *mesh = loaded;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the scene as JSON. Paths to images, meshes and models are
+    /// written relative to the file's directory.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> error::Result<()> {
+        reject_embedded_images(&self.texture_cache)?;
+        save_json(self, path.as_ref())
+    }
+
+    /// Checks that every index in the scene points to an entry of its cache.
+    /// Models aren't imported, so only the indices written by hand are
+    /// checked.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validation::validate(
+            &self.sky_box,
+            &self.texture_cache,
+            &self.geometry_cache,
+            &self.material_cache,
+            &self.items,
+        )
+    }
+}
+
 impl Scene {
-    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+    /// Loads a scene from a glTF 2.0 file if the path ends with .gltf or .glb.
+    /// Otherwise the file is read as a JSON scene description. Files that the
+    /// JSON refers to by relative paths are looked up as `AssetPaths`
+    /// describes.
+    pub fn load<P: AsRef<Path>>(path: P, search_paths: &[PathBuf]) -> error::Result<Self> {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+
+        match extension.as_deref() {
+            Some("gltf") | Some("glb") => import::gltf::load(path),
+            _ => Self::from_json(path, search_paths),
+        }
+    }
+
+    pub fn from_json<P: AsRef<Path>>(path: P, search_paths: &[PathBuf]) -> error::Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|err| Error::io(path, err))?;
+
+        // Names are resolved separately so that unknown ones are reported as
+        // invalid references rather than parse errors.
+        let mut value: Value =
+            serde_json::from_str(&contents).map_err(|err| Error::parse(path, err))?;
+        names::resolve_names(&mut value)?;
+
+        let description = match SceneDescription::deserialize(value) {
+            Ok(description) => description,
+            // Values don't know where they were in the file. Parsing the file
+            // again finds the position of the error, unless a name trips the
+            // parser up first.
+            Err(err) => {
+                let mut deserializer = serde_json::Deserializer::from_str(&contents);
+                let err = match SceneDescription::deserialize(&mut deserializer) {
+                    Err(located) if located.to_string().starts_with(&err.to_string()) => located,
+                    _ => err,
+                };
+                return Err(Error::parse(path, err));
+            }
+        };
 
-        Ok(serde_json::from_str(&contents)?)
+        let assets = AssetPaths {
+            scene_dir: path.parent().unwrap_or_else(|| Path::new("")).to_owned(),
+            search_paths: search_paths.to_vec(),
+        };
+        Scene::new(description, &assets)
     }
 
-    pub fn hit(&self, ray: &Ray3, tmin: f32, tmax: f32) -> Option<HitInfoAndMaterial> {
-        let mut closest_hit: Option<HitInfoAndMaterial> = None;
+    /// Imports the description's models, loads its images and meshes and
+    /// builds a BVH over its items.
+    pub fn new(mut description: SceneDescription, assets: &AssetPaths) -> error::Result<Self> {
+        for model in std::mem::take(&mut description.models) {
+            model.import(&mut description, assets)?;
+        }
+
+        description.validate()?;
+        description.load_images(assets)?;
+        description.load_meshes(assets)?;
+
+        let aabbs: Vec<Aabb> = description
+            .items
+            .iter()
+            .map(|item| item.aabb(&description.geometry_cache))
+            .collect();
 
-        for hit in self
+        let lights = description
             .items
             .iter()
-            .filter_map(|item| self.hit_item(item, ray, tmin, tmax))
+            .enumerate()
+            .filter(|(_, item)| {
+                matches!(
+                    description.material_cache[item.material_idx],
+                    Material::Light(_)
+                ) && description.geometry_cache[item.geometry_idx].surface_pdf() > 0.0
+            })
+            .map(|(item_idx, _)| item_idx)
+            .collect();
+
+        Ok(Scene {
+            sky_box: description.sky_box,
+            camera: description.camera,
+            texture_cache: description.texture_cache,
+            geometry_cache: description.geometry_cache,
+            material_cache: description.material_cache,
+            items: description.items,
+            bvh: Bvh::new(&aabbs),
+            lights,
+        })
+    }
+
+    /// Returns how much memory the decoded images of the scene's textures
+    /// take. Images shared by several textures are counted once.
+    pub fn image_memory(&self) -> ImageMemory {
+        let mut images = HashSet::new();
+        let mut memory = ImageMemory::default();
+
+        for texture in self.texture_cache.iter() {
+            if let Some(image) = match texture {
+                Texture::Image(image) => image.mipmap(),
+                _ => None,
+            } {
+                memory.textures += 1;
+
+                if images.insert(Arc::as_ptr(image)) {
+                    memory.images += 1;
+                    memory.bytes += image.bytes();
+                }
+            }
+        }
+
+        memory
+    }
+
+    /// Writes the scene as JSON. Paths to images and meshes are written
+    /// relative to the file's directory.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> error::Result<()> {
+        reject_embedded_images(&self.texture_cache)?;
+        save_json(self, path.as_ref())
+    }
+
+    /// Checks that every index in the scene points to an entry of its cache.
+    /// Scenes are validated when they're loaded, so this only fails if they
+    /// were changed afterwards.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validation::validate(
+            &self.sky_box,
+            &self.texture_cache,
+            &self.geometry_cache,
+            &self.material_cache,
+            &self.items,
+        )
+    }
+
+    pub fn hit(&self, ray: &Ray3, tmin: f32, tmax: f32) -> Option<HitInfoAndMaterial<'_>> {
+        self.hit_item(ray, tmin, tmax)
+            .map(|(item_idx, hit_info)| HitInfoAndMaterial(hit_info, self.material_of(item_idx)))
+    }
+
+    /// Like `hit` but returns the index of the item which was hit.
+    pub fn hit_item(&self, ray: &Ray3, tmin: f32, tmax: f32) -> Option<(usize, HitInfo)> {
+        self.bvh.hit(ray, tmin, tmax, |item_idx, tmax| {
+            self.items[item_idx].hit(&self.geometry_cache, ray, tmin, tmax)
+        })
+    }
+
+    pub fn material_of(&self, item_idx: usize) -> &Material {
+        &self.material_cache[self.items[item_idx].material_idx]
+    }
+
+    /// Picks a light uniformly and then a point uniformly on its surface.
+    pub fn sample_light<R: Rng>(&self, pos: Vec3, rng: &mut R) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+
+        let item_idx = self.lights[rng.gen_range(0..self.lights.len())];
+        let sample = self.items[item_idx].sample_surface(&self.geometry_cache, rng)?;
+
+        let dir = sample.pos - pos;
+        let cos = sample.normal.dot(dir.normalize()).abs();
+
+        if cos <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            item_idx,
+            dir,
+            pdf: sample.pdf * dir.length_squared() / cos / self.lights.len() as f32,
+        })
+    }
+
+    /// The probability density (per unit solid angle) with which
+    /// `sample_light` picks the point where `ray` hit the item. It's 0 for
+    /// items which aren't lights or can't be sampled.
+    pub fn light_pdf(&self, item_idx: usize, ray: &Ray3, hit: &HitInfo) -> f32 {
+        if !matches!(self.material_of(item_idx), Material::Light(_)) {
+            return 0.0;
+        }
+
+        let dir = hit.pos - ray.pos;
+        let cos = hit.tbn.n.dot(dir.normalize()).abs();
+        let pdf = self.items[item_idx].surface_pdf(&self.geometry_cache, hit);
+
+        if cos <= 0.0 || pdf <= 0.0 {
+            return 0.0;
+        }
+
+        pdf * dir.length_squared() / cos / self.lights.len() as f32
+    }
+}
+
+// The derived implementations (with `remote = "Self"`) handle indices only.
+// These wrap them to swap names and indices.
+
+impl Serialize for SceneDescription {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value = SceneDescription::serialize(self, serde_json::value::Serializer)
+            .map_err(ser::Error::custom)?;
+        names::use_names(&mut value);
+        value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SceneDescription {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut value = Value::deserialize(deserializer)?;
+        names::resolve_names(&mut value).map_err(de::Error::custom)?;
+        SceneDescription::deserialize(value).map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for Scene {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut value =
+            Scene::serialize(self, serde_json::value::Serializer).map_err(ser::Error::custom)?;
+        names::use_names(&mut value);
+        value.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Scene {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let description = <SceneDescription as Deserialize>::deserialize(deserializer)?;
+        Scene::try_from(description).map_err(de::Error::custom)
+    }
+}
+
+/// Relative paths are looked up in the current directory.
+impl TryFrom<SceneDescription> for Scene {
+    type Error = Error;
+
+    fn try_from(description: SceneDescription) -> error::Result<Self> {
+        Scene::new(description, &AssetPaths::default())
+    }
+}
+
+/// Scene files can only point to images, so embedded ones can't be saved.
+fn reject_embedded_images(texture_cache: &Cache<Texture>) -> error::Result<()> {
+    for texture in texture_cache.iter() {
+        if let Texture::Image(image) = texture {
+            if image.is_embedded() {
+                return Err(Error::EmbeddedImage {
+                    path: image.path.clone().into(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn save_json<T: Serialize>(scene: &T, path: &Path) -> error::Result<()> {
+    let mut value = serde_json::to_value(scene).expect("Couldn't serialize scene");
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+
+    // Images are in the texture cache, meshes in the geometry cache and
+    // models in the list of models.
+    for (list, path_in_entry) in [
+        ("/texture_cache/items", "/Image/path"),
+        ("/geometry_cache/items", "/Mesh/path"),
+        ("/models", "/path"),
+    ] {
+        let entries = value.pointer_mut(list).and_then(Value::as_array_mut);
+
+        for asset_path in entries
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.pointer_mut(path_in_entry))
         {
-            closest_hit = match closest_hit {
-                Some(closest_hit) if hit.0.t < closest_hit.0.t => Some(hit),
-                None => Some(hit),
-                _ => closest_hit,
-            };
+            let relative = asset_path
+                .as_str()
+                .and_then(|asset_path| assets::relative_path(asset_path, dir));
+
+            if let Some(relative) = relative.as_ref().and_then(|relative| relative.to_str()) {
+                *asset_path = Value::from(relative);
+            }
         }
+    }
 
-        closest_hit
+    let json = serde_json::to_string_pretty(&value).expect("Couldn't serialize scene");
+    fs::write(path, json).map_err(|err| Error::io(path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn textures_of_the_same_file_share_its_image() {
+        let mut texture_cache = Cache::new();
+        for (name, path) in [("uv", "uv-map-test.png"), ("same_uv", "./uv-map-test.png")] {
+            let json = format!(r#"{{"path": "{}"}}"#, path);
+            texture_cache.add(name, Texture::Image(serde_json::from_str(&json).unwrap()));
+        }
+
+        let description = SceneDescription {
+            sky_box: SkyBox::Cubemap(crate::sky_box::Cubemap::all(0)),
+            camera: serde_json::from_str(
+                r#"{"pos": [0, 0, 1], "look_at": [0, 0, 0], "vup": [0, 1, 0], "vt_fov": 40, "aspect": 2}"#,
+            )
+            .unwrap(),
+            texture_cache,
+            geometry_cache: Cache::new(),
+            material_cache: Cache::new(),
+            items: vec![],
+            models: vec![],
+        };
+        let assets = AssetPaths {
+            scene_dir: Path::new(env!("CARGO_MANIFEST_DIR")).join("inputs/textures"),
+            search_paths: vec![],
+        };
+        let scene = Scene::new(description, &assets).unwrap();
+
+        let memory = scene.image_memory();
+        assert_eq!(2, memory.textures);
+        assert_eq!(1, memory.images);
+        assert!(memory.bytes > 0);
     }
 
-    fn hit_item(
-        &self,
-        item: &Item,
-        ray: &Ray3,
-        tmin: f32,
-        tmax: f32,
-    ) -> Option<HitInfoAndMaterial> {
-        self.geometry_cache[item.geometry_idx]
-            .hit(ray, tmin, tmax)
-            .map(|hit_info| HitInfoAndMaterial(hit_info, &self.material_cache[item.material_idx]))
+    #[test]
+    fn meshes_are_found_after_saving_the_scene_elsewhere() {
+        use crate::material::Lambertian;
+        use crate::texture::Solid;
+
+        let root = std::env::temp_dir().join("raytracer_saved_mesh");
+        for dir in ["models", "scenes", "saved"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        fs::write(
+            root.join("models/triangle.obj"),
+            "v -1 -1 0\nv 1 -1 0\nv 0 1 0\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let mut texture_cache = Cache::new();
+        let texture_idx = texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+        let mut geometry_cache = Cache::new();
+        let geometry_idx = geometry_cache.add(
+            "triangle",
+            serde_json::from_str(r#"{"Mesh": {"path": "../models/triangle.obj"}}"#).unwrap(),
+        );
+        let mut material_cache = Cache::new();
+        let material_idx = material_cache.add(
+            "white",
+            Material::Lambertian(Lambertian {
+                texture_idx,
+                normal_map_idx: None,
+                emission: None,
+            }),
+        );
+
+        let description = SceneDescription {
+            sky_box: SkyBox::Cubemap(crate::sky_box::Cubemap::all(0)),
+            camera: serde_json::from_str(
+                r#"{"pos": [0, 0, 1], "look_at": [0, 0, 0], "vup": [0, 1, 0], "vt_fov": 40, "aspect": 2}"#,
+            )
+            .unwrap(),
+            texture_cache,
+            geometry_cache,
+            material_cache,
+            items: vec![Item {
+                geometry_idx,
+                material_idx,
+                transform: None,
+            }],
+            models: vec![],
+        };
+        let scene_path = root.join("scenes/scene.json");
+        fs::write(&scene_path, serde_json::to_string(&description).unwrap()).unwrap();
+
+        let scene = Scene::from_json(&scene_path, &[]).unwrap();
+        let saved_path = root.join("saved/scene.json");
+        scene.save(&saved_path).unwrap();
+        let scene = Scene::from_json(&saved_path, &[]).unwrap();
+
+        let ray = Ray3::new(Vec3::new(0.0, 0.0, 1.0), -Vec3::Z);
+        assert_eq!(1.0, scene.hit(&ray, 0.0, f32::MAX).unwrap().0.t);
+    }
+
+    #[test]
+    fn scenes_with_embedded_images_are_not_saved() {
+        use image::{DynamicImage, RgbImage};
+
+        use crate::texture::{Image, MipMap};
+
+        let mut texture_cache = Cache::new();
+        let image = DynamicImage::ImageRgb8(RgbImage::new(1, 1));
+        texture_cache.add(
+            "embedded",
+            Texture::Image(Image::embedded(
+                "model.glb#image0".to_owned(),
+                MipMap::new(image),
+            )),
+        );
+
+        let description = SceneDescription {
+            sky_box: SkyBox::Cubemap(crate::sky_box::Cubemap::all(0)),
+            camera: serde_json::from_str(
+                r#"{"pos": [0, 0, 1], "look_at": [0, 0, 0], "vup": [0, 1, 0], "vt_fov": 40, "aspect": 2}"#,
+            )
+            .unwrap(),
+            texture_cache,
+            geometry_cache: Cache::new(),
+            material_cache: Cache::new(),
+            items: vec![],
+            models: vec![],
+        };
+        let path = std::env::temp_dir().join("raytracer_embedded_image.json");
+
+        match description.save(&path) {
+            Err(Error::EmbeddedImage { path }) => assert_eq!(Path::new("model.glb#image0"), path),
+            result => panic!("Expected an embedded image error, got {:?}", result),
+        }
+        assert!(!path.exists());
     }
 }
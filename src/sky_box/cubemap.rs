@@ -4,7 +4,7 @@ use glam::Vec3;
 use serde::{Deserialize, Serialize};
 
 use crate::cache::Cache;
-use crate::texture::Texture;
+use crate::texture::{TexCoord, Texture};
 use crate::util::map;
 
 #[derive(Serialize, Deserialize)]
@@ -67,7 +67,14 @@ impl Cubemap {
             Dir::Bk => self.bk_idx,
         };
         let (u, v) = dir.uv(x, y, z);
-        texture_cache[tex_idx].color(u, v)
+        let coord = TexCoord::new(u, v);
+
+        match &texture_cache[tex_idx] {
+            // The edges of a face meet other faces, not the face's opposite
+            // edge, so filtering mustn't wrap around.
+            Texture::Image(image) => image.clamped_color(coord),
+            texture => texture.color(texture_cache, coord),
+        }
     }
 }
 
@@ -124,4 +131,26 @@ mod tests {
         let bot_rt = Vec3::new(1.0, -1.0, -1.0).normalize();
         assert_eq!((1.0, 1.0), Dir::Ft.uv(bot_rt.x, bot_rt.y, bot_rt.z));
     }
+
+    #[test]
+    fn faces_do_not_wrap_around_their_edges() {
+        use image::{DynamicImage, Rgb, RgbImage};
+
+        use crate::texture::{ColorSpace, Filter, Image, MipMap, Sampler};
+
+        // Black on the left and white on the right.
+        let pixels = RgbImage::from_fn(2, 1, |x, _| Rgb([x as u8 * 255; 3]));
+        let mut image = Image::new(String::new(), MipMap::new(DynamicImage::ImageRgb8(pixels)));
+        image.sampler = Sampler {
+            filter: Filter::Bilinear,
+            ..Sampler::default()
+        };
+        image.color_space = Some(ColorSpace::Linear);
+
+        let mut texture_cache = Cache::new();
+        let cubemap = Cubemap::all(texture_cache.add("face", Texture::Image(image)));
+
+        let left_edge = Vec3::new(-0.999, 0.0, -1.0);
+        assert_eq!(Vec3::ZERO, cubemap.color(&texture_cache, left_edge));
+    }
 }
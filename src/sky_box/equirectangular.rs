@@ -2,7 +2,7 @@ use glam::Vec3;
 use serde::{Deserialize, Serialize};
 
 use crate::cache::Cache;
-use crate::texture::Texture;
+use crate::texture::{TexCoord, Texture};
 use crate::util::compute_uv_on_sphere_from_normal;
 
 #[derive(Serialize, Deserialize)]
@@ -12,7 +12,7 @@ pub struct Equirectangular {
 
 impl Equirectangular {
     pub fn color(&self, texture_cache: &Cache<Texture>, dir: Vec3) -> Vec3 {
-        let (u, v) = compute_uv_on_sphere_from_normal(dir);
-        texture_cache[self.tex_idx].color(u, v)
+        let (u, v) = compute_uv_on_sphere_from_normal(dir.normalize());
+        texture_cache[self.tex_idx].color(texture_cache, TexCoord::new(u, v))
     }
 }
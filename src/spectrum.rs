@@ -0,0 +1,176 @@
+//! Spectral rendering with hero wavelengths. Every camera ray stands for four
+//! wavelengths of visible light, evenly spread with a random offset. They
+//! share the ray's path until it meets a material whose behaviour depends on
+//! the wavelength (like glass with dispersion). From there on only the first
+//! one (the hero) is followed.
+//!
+//! Materials still work with RGB colors. Each wavelength turns into an RGB
+//! weight, and a path is weighted by the average of the weights of the
+//! wavelengths it carries. On average over wavelengths, the weights are white.
+//! So scenes without dispersion look the same as in RGB.
+
+use std::sync::OnceLock;
+
+use glam::Vec3;
+use rand::Rng;
+
+/// The range of visible wavelengths (in nanometers) which are sampled.
+pub const MIN_WAVELENGTH: f32 = 380.0;
+pub const MAX_WAVELENGTH: f32 = 780.0;
+
+const NUM_WAVELENGTHS: usize = 4;
+
+/// The wavelengths (in nanometers) a ray carries.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Wavelengths {
+    pub hero: f32,
+    /// Set once the other wavelengths were dropped.
+    pub hero_only: bool,
+}
+
+impl Wavelengths {
+    /// Picks a hero wavelength uniformly at random.
+    pub fn sample<R: Rng>(rng: &mut R) -> Self {
+        Wavelengths {
+            hero: rng.gen_range(MIN_WAVELENGTH..MAX_WAVELENGTH),
+            hero_only: false,
+        }
+    }
+
+    /// The hero followed by the others, each a quarter of the visible range
+    /// further (wrapping around at the end).
+    pub fn all(&self) -> [f32; NUM_WAVELENGTHS] {
+        let range = MAX_WAVELENGTH - MIN_WAVELENGTH;
+        let mut wavelengths = [self.hero; NUM_WAVELENGTHS];
+
+        for (i, wavelength) in wavelengths.iter_mut().enumerate() {
+            let offset = range * i as f32 / NUM_WAVELENGTHS as f32;
+            *wavelength = MIN_WAVELENGTH + (self.hero - MIN_WAVELENGTH + offset) % range;
+        }
+
+        wavelengths
+    }
+
+    /// The RGB weight of the light carried by a ray.
+    pub fn weight(&self) -> Vec3 {
+        if self.hero_only {
+            rgb_weight(self.hero)
+        } else {
+            self.all()
+                .iter()
+                .fold(Vec3::ZERO, |sum, &wavelength| sum + rgb_weight(wavelength))
+                / NUM_WAVELENGTHS as f32
+        }
+    }
+
+    /// Drops all wavelengths but the hero. Returns the factor that the
+    /// light of the ray has to be multiplied with to make up for its weight
+    /// changing.
+    pub fn keep_hero(&mut self) -> Vec3 {
+        if self.hero_only {
+            return Vec3::ONE;
+        }
+
+        let shared_weight = self.weight();
+        self.hero_only = true;
+        let hero_weight = self.weight();
+
+        // The hero's weight is at most 4 times the shared one. Channels where
+        // the shared weight is 0 have a hero weight of 0 too.
+        let ratio = |hero: f32, shared: f32| if shared > 0.0 { hero / shared } else { 0.0 };
+        Vec3::new(
+            ratio(hero_weight.x, shared_weight.x),
+            ratio(hero_weight.y, shared_weight.y),
+            ratio(hero_weight.z, shared_weight.z),
+        )
+    }
+}
+
+/// The linear sRGB color of light of a single wavelength, scaled so that the
+/// average over the visible range is white.
+pub fn rgb_weight(wavelength: f32) -> Vec3 {
+    static AVERAGE: OnceLock<Vec3> = OnceLock::new();
+
+    let average = AVERAGE.get_or_init(|| {
+        let num_steps = 4000;
+        let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / num_steps as f32;
+
+        (0..num_steps).fold(Vec3::ZERO, |sum, i| {
+            sum + unscaled_rgb(MIN_WAVELENGTH + (i as f32 + 0.5) * step)
+        }) / num_steps as f32
+    });
+
+    unscaled_rgb(wavelength) / *average
+}
+
+/// Colors outside of the sRGB gamut (which most spectral colors are) are
+/// clamped to it.
+fn unscaled_rgb(wavelength: f32) -> Vec3 {
+    let xyz = xyz(wavelength);
+
+    Vec3::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+    .max(Vec3::ZERO)
+}
+
+/// The CIE 1931 color matching functions. It's the multi-lobe fit from
+/// Wyman, Sloan and Shirley's "Simple Analytic Approximations to the CIE XYZ
+/// Color Matching Functions".
+fn xyz(wavelength: f32) -> Vec3 {
+    let lobe = |mean: f32, left_width: f32, right_width: f32| {
+        let width = if wavelength < mean {
+            left_width
+        } else {
+            right_width
+        };
+        (-0.5 * ((wavelength - mean) / width).powi(2)).exp()
+    };
+
+    Vec3::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand_xoshiro::Xoshiro256PlusPlus;
+
+    use super::*;
+
+    #[test]
+    fn wavelengths_average_to_white() {
+        let mut rng = Xoshiro256PlusPlus::seed_from_u64(0);
+        let num_samples = 10_000;
+
+        let average = (0..num_samples).fold(Vec3::ZERO, |sum, _| {
+            sum + Wavelengths::sample(&mut rng).weight()
+        }) / num_samples as f32;
+
+        assert!(average.abs_diff_eq(Vec3::ONE, 0.02), "{}", average);
+    }
+
+    #[test]
+    fn keeping_the_hero_reweights_it() {
+        let mut wavelengths = Wavelengths {
+            hero: 650.0,
+            hero_only: false,
+        };
+        assert_eq!([650.0, 750.0, 450.0, 550.0], wavelengths.all());
+
+        let shared_weight = wavelengths.weight();
+        let factor = wavelengths.keep_hero();
+
+        assert!((shared_weight * factor).abs_diff_eq(rgb_weight(650.0), 1e-5));
+        // Red light has no blue in it.
+        assert!(rgb_weight(650.0).x > 1.0);
+        assert_eq!(0.0, rgb_weight(650.0).z);
+        assert_eq!(Vec3::ONE, wavelengths.keep_hero());
+    }
+}
@@ -0,0 +1,94 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::Cache;
+
+use super::{TexCoord, Texture};
+
+/// Alternates between two textures in squares (or cubes). The squares are
+/// `1 / scale` wide, so a scale of 8 makes an 8x8 board across the UVs.
+#[derive(Serialize, Deserialize)]
+pub struct Checker {
+    pub even_idx: usize,
+    pub odd_idx: usize,
+    pub scale: f32,
+    #[serde(default)]
+    pub domain: Domain,
+}
+
+/// Where a pattern is laid out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Domain {
+    /// On the surface's UVs, so the pattern follows the surface.
+    #[default]
+    Uv,
+    /// In the scene space, so the pattern is carved out of a solid block.
+    Scene,
+}
+
+impl Checker {
+    pub fn color(&self, texture_cache: &Cache<Texture>, coord: TexCoord) -> Vec3 {
+        let cell = match self.domain {
+            Domain::Uv => (Vec3::new(coord.u, coord.v, 0.0) * self.scale).floor(),
+            Domain::Scene => (coord.pos * self.scale).floor(),
+        };
+
+        let idx = if (cell.x + cell.y + cell.z).rem_euclid(2.0) == 0.0 {
+            self.even_idx
+        } else {
+            self.odd_idx
+        };
+
+        texture_cache[idx].color(texture_cache, coord)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::texture::Solid;
+
+    use super::*;
+
+    /// Returns a black and white checker and its texture cache.
+    fn checker(domain: Domain) -> (Checker, Cache<Texture>) {
+        let mut texture_cache = Cache::new();
+        let even_idx = texture_cache.add("black", Texture::Solid(Solid { color: Vec3::ZERO }));
+        let odd_idx = texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+
+        let checker = Checker {
+            even_idx,
+            odd_idx,
+            scale: 2.0,
+            domain,
+        };
+        (checker, texture_cache)
+    }
+
+    #[test]
+    fn cells_alternate_across_negative_coordinates() {
+        let (checker, texture_cache) = checker(Domain::Uv);
+        let color = |u, v| checker.color(&texture_cache, TexCoord::new(u, v));
+
+        assert_eq!(Vec3::ZERO, color(0.25, 0.25));
+        assert_eq!(Vec3::ONE, color(-0.25, 0.25));
+        assert_eq!(Vec3::ZERO, color(-0.25, -0.25));
+        assert_eq!(Vec3::ONE, color(-0.75, -0.25));
+    }
+
+    #[test]
+    fn scene_domain_ignores_the_uvs() {
+        let (checker, texture_cache) = checker(Domain::Scene);
+        let color = |u, v, pos| {
+            let coord = TexCoord {
+                pos,
+                ..TexCoord::new(u, v)
+            };
+            checker.color(&texture_cache, coord)
+        };
+
+        let pos = Vec3::new(0.25, 0.25, -0.25);
+        assert_eq!(Vec3::ONE, color(0.25, 0.25, pos));
+        assert_eq!(Vec3::ONE, color(0.75, 0.25, pos));
+        assert_eq!(Vec3::ZERO, color(0.25, 0.25, Vec3::splat(0.25)));
+    }
+}
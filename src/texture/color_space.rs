@@ -0,0 +1,96 @@
+use std::sync::OnceLock;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+/// How the values stored in an image relate to the light they stand for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ColorSpace {
+    /// Values are proportional to light. Normal maps, roughness maps and
+    /// float images (like OpenEXR and Radiance HDR ones) are usually linear.
+    Linear,
+    /// Values are encoded with the sRGB transfer function, which spends more
+    /// of them on dark colors. Most 8 bit color images (like photos and
+    /// painted albedo textures) are sRGB.
+    Srgb,
+}
+
+impl ColorSpace {
+    /// Turns values stored in this color space into linear ones.
+    pub fn decode(self, color: Vec3) -> Vec3 {
+        match self {
+            Self::Linear => color,
+            Self::Srgb => Vec3::new(
+                srgb_to_linear(color.x),
+                srgb_to_linear(color.y),
+                srgb_to_linear(color.z),
+            ),
+        }
+    }
+
+    /// Turns linear values into ones stored in this color space.
+    pub fn encode(self, color: Vec3) -> Vec3 {
+        match self {
+            Self::Linear => color,
+            Self::Srgb => Vec3::new(
+                linear_to_srgb(color.x),
+                linear_to_srgb(color.y),
+                linear_to_srgb(color.z),
+            ),
+        }
+    }
+
+    /// Like `decode` for a single 8 bit channel. It's a table lookup.
+    pub fn decode_u8(self, value: u8) -> f32 {
+        static SRGB_TO_LINEAR: OnceLock<[f32; 256]> = OnceLock::new();
+
+        match self {
+            Self::Linear => value as f32 / 255.0,
+            Self::Srgb => SRGB_TO_LINEAR.get_or_init(|| {
+                let mut table = [0.0; 256];
+                for (value, linear) in table.iter_mut().enumerate() {
+                    *linear = srgb_to_linear(value as f32 / 255.0);
+                }
+                table
+            })[value as usize],
+        }
+    }
+}
+
+/// The sRGB transfer function's inverse: a short linear segment near black
+/// and a 2.4 power curve above it.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips() {
+        for value in [0.0, 0.002, 0.04, 0.2, 0.5, 0.9, 1.0] {
+            assert!((linear_to_srgb(srgb_to_linear(value)) - value).abs() < 1e-5);
+        }
+
+        // Middle gray is much darker in linear terms.
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 0.001);
+        assert_eq!(
+            srgb_to_linear(128.0 / 255.0),
+            ColorSpace::Srgb.decode_u8(128)
+        );
+        assert_eq!(1.0, ColorSpace::Linear.decode_u8(255));
+    }
+}
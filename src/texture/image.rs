@@ -1,49 +1,175 @@
-use std::convert::TryFrom;
+use std::io;
 use std::path::Path;
+use std::sync::Arc;
 
 use glam::Vec3;
-use image::{io::Reader, DynamicImage, GenericImageView, ImageError, ImageResult};
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize)]
-struct TexturePath {
-    path: String,
-}
+use crate::error::{Error, Result};
+
+use super::{ColorSpace, MipMap, Sampler, TexCoord, Wrap};
 
+/// A texture read from an image file. Deserializing it only reads the path.
+/// The image is decoded when the scene is loaded, so that a missing or broken
+/// file is reported as such.
+///
+/// Decoded images are shared by all the textures made from the same file.
 #[derive(Serialize, Deserialize)]
-#[serde(try_from = "TexturePath")]
 pub struct Image {
     pub path: String,
-    #[serde(skip_serializing)]
-    image: DynamicImage,
+    #[serde(default)]
+    pub sampler: Sampler,
+    /// The color space of the image's pixels. Without it, float images are
+    /// linear, other images are sRGB when used for colors and linear when used
+    /// as normal maps.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_space: Option<ColorSpace>,
+    /// Multiplies the linear colors of the image. Data like normal maps isn't
+    /// tinted.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub tint: Vec3,
+    /// Embedded images (like the ones inside glTF files) have no file of
+    /// their own, so scenes using them can't be saved.
+    #[serde(skip)]
+    embedded: bool,
+    #[serde(skip)]
+    mipmap: Option<Arc<MipMap>>,
+}
+
+fn one() -> Vec3 {
+    Vec3::ONE
+}
+
+fn is_one(tint: &Vec3) -> bool {
+    *tint == Vec3::ONE
 }
 
 impl Image {
-    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
-        let image = Reader::open(&path)?.decode()?;
-        let channels = image.color().channel_count() as usize;
+    /// Wraps an image which was decoded elsewhere, like one embedded in a glTF
+    /// file, or one shared with another texture. `path` is only used when
+    /// serializing the image.
+    pub fn new<M: Into<Arc<MipMap>>>(path: String, mipmap: M) -> Self {
+        Image {
+            path,
+            sampler: Sampler::default(),
+            color_space: None,
+            tint: Vec3::ONE,
+            embedded: false,
+            mipmap: Some(mipmap.into()),
+        }
+    }
+
+    /// Wraps an image which is stored inside another file. `name` only
+    /// identifies it in error messages.
+    pub fn embedded<M: Into<Arc<MipMap>>>(name: String, mipmap: M) -> Self {
+        Image {
+            embedded: true,
+            ..Image::new(name, mipmap)
+        }
+    }
+
+    /// Returns a copy of the texture which shares its decoded image but
+    /// multiplies its colors by `tint` too.
+    pub fn tinted(&self, tint: Vec3) -> Self {
+        Image {
+            path: self.path.clone(),
+            sampler: self.sampler.clone(),
+            color_space: self.color_space,
+            tint: self.tint * tint,
+            embedded: self.embedded,
+            mipmap: self.mipmap.clone(),
+        }
+    }
+
+    pub fn is_embedded(&self) -> bool {
+        self.embedded
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
 
-        assert!(channels == 3 || channels == 4);
+        let name = path.to_str().ok_or_else(|| {
+            Error::io(
+                path,
+                io::Error::new(io::ErrorKind::InvalidInput, "Non UTF-8 path"),
+            )
+        })?;
+
+        Ok(Image::new(name.to_owned(), MipMap::load(path)?))
+    }
 
-        Ok(Image {
-            path: path.as_ref().to_str().expect("Non UTF-8 path").to_owned(),
-            image,
-        })
+    pub fn is_loaded(&self) -> bool {
+        self.mipmap.is_some()
     }
 
-    pub fn color(&self, u: f32, v: f32) -> Vec3 {
-        let (wd, ht) = self.image.dimensions();
-        let x = (u * (wd - 1) as f32) as u32;
-        let y = (v * (ht - 1) as f32) as u32;
-        let [r, g, b, _] = self.image.get_pixel(x, y).0;
-        Vec3::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    /// Returns the decoded image, if it was loaded already.
+    pub fn mipmap(&self) -> Option<&Arc<MipMap>> {
+        self.mipmap.as_ref()
+    }
+
+    pub fn set_mipmap(&mut self, mipmap: Arc<MipMap>) {
+        self.mipmap = Some(mipmap);
+    }
+
+    /// Returns the linear color of the image around `coord`.
+    pub fn color(&self, coord: TexCoord) -> Vec3 {
+        let mipmap = self.loaded_mipmap();
+        let color_space = self.color_space.unwrap_or_else(|| mipmap.color_space());
+        self.sampler.sample(mipmap, color_space, coord) * self.tint
+    }
+
+    /// Like `color` but clamps the UVs to the image whatever the sampler's
+    /// wrap mode is. Used by images whose edges don't continue on their
+    /// opposite edges, like the faces of a cubemap.
+    pub fn clamped_color(&self, coord: TexCoord) -> Vec3 {
+        let mipmap = self.loaded_mipmap();
+        let color_space = self.color_space.unwrap_or_else(|| mipmap.color_space());
+        let sampler = Sampler {
+            wrap: Wrap::Clamp,
+            ..self.sampler.clone()
+        };
+        sampler.sample(mipmap, color_space, coord) * self.tint
+    }
+
+    /// Returns the values of the image around `coord`, for images which hold
+    /// data other than colors (like normal maps). They're linear unless the
+    /// image is tagged otherwise.
+    pub fn data(&self, coord: TexCoord) -> Vec3 {
+        let color_space = self.color_space.unwrap_or(ColorSpace::Linear);
+        self.sampler
+            .sample(self.loaded_mipmap(), color_space, coord)
+    }
+
+    fn loaded_mipmap(&self) -> &MipMap {
+        self.mipmap.as_ref().expect("Image isn't loaded")
     }
 }
 
-impl TryFrom<TexturePath> for Image {
-    type Error = ImageError;
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn tint_multiplies_colors_but_not_data() {
+        let pixels = RgbImage::from_pixel(1, 1, Rgb([255, 255, 255]));
+        let image = Image::new(String::new(), MipMap::new(DynamicImage::ImageRgb8(pixels)));
+        let tinted = image.tinted(Vec3::new(1.0, 0.5, 0.0));
+        let coord = TexCoord::new(0.5, 0.5);
+
+        assert_eq!(Vec3::new(1.0, 0.5, 0.0), tinted.color(coord));
+        assert_eq!(Vec3::ONE, tinted.data(coord));
+        assert_eq!(Vec3::ONE, image.color(coord));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_path_is_an_error() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
 
-    fn try_from(path: TexturePath) -> Result<Self, Self::Error> {
-        Image::load(path.path)
+        let path = Path::new(OsStr::from_bytes(b"texture\xff.png"));
+        assert!(matches!(Image::load(path), Err(Error::Io { .. })));
     }
 }
@@ -0,0 +1,80 @@
+use std::f32::consts::TAU;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use super::perlin;
+use super::TexCoord;
+
+/// Veins across the x axis of the scene space, made wavy with turbulence.
+/// There are about `scale` veins per unit.
+#[derive(Serialize, Deserialize)]
+pub struct Marble {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub scale: f32,
+    /// How far the veins are pushed around by the turbulence.
+    pub distortion: f32,
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+}
+
+fn default_octaves() -> u32 {
+    6
+}
+
+impl Marble {
+    pub fn color(&self, coord: TexCoord) -> Vec3 {
+        let pos = coord.pos * self.scale;
+        let phase = TAU * pos.x + self.distortion * perlin::turbulence(pos, self.octaves);
+
+        self.from.lerp(self.to, (1.0 + phase.sin()) / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 0.0001;
+
+    fn at(pos: Vec3) -> TexCoord {
+        TexCoord {
+            pos,
+            ..TexCoord::new(0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn undistorted_veins_run_across_x() {
+        let marble = Marble {
+            from: Vec3::ZERO,
+            to: Vec3::ONE,
+            scale: 2.0,
+            distortion: 0.0,
+            octaves: 6,
+        };
+
+        for (x, expected) in [(0.125, Vec3::ONE), (0.375, Vec3::ZERO), (0.625, Vec3::ONE)] {
+            for (y, z) in [(0.0, 0.0), (1.3, -4.2)] {
+                let color = marble.color(at(Vec3::new(x, y, z)));
+                assert!(color.abs_diff_eq(expected, EPS), "{} at x = {}", color, x);
+            }
+        }
+    }
+
+    #[test]
+    fn distortion_moves_the_veins() {
+        let marble = |distortion| Marble {
+            from: Vec3::ZERO,
+            to: Vec3::ONE,
+            scale: 2.0,
+            distortion,
+            octaves: 6,
+        };
+        let pos = Vec3::new(0.125, 0.3, 0.7);
+
+        let color = marble(4.0).color(at(pos));
+        assert!(!color.abs_diff_eq(marble(0.0).color(at(pos)), EPS));
+    }
+}
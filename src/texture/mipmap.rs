@@ -0,0 +1,224 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use glam::Vec3;
+use image::imageops::{self, FilterType};
+use image::io::Reader;
+use image::{DynamicImage, GenericImageView, Pixel, Rgb, Rgb32FImage};
+
+use crate::error::{Error, Result};
+
+use super::ColorSpace;
+
+/// A decoded image along with smaller and smaller copies of it, each half as
+/// wide and high as the one before. The copies (levels) are only made the
+/// first time they're needed.
+///
+/// Levels are averages of linear colors, so they're stored as linear floats.
+/// An image can be read in either color space, so each one gets its own
+/// levels.
+pub struct MipMap {
+    image: DynamicImage,
+    linear_levels: OnceLock<Vec<Rgb32FImage>>,
+    srgb_levels: OnceLock<Vec<Rgb32FImage>>,
+}
+
+impl MipMap {
+    pub fn new(image: DynamicImage) -> Self {
+        MipMap {
+            image,
+            linear_levels: OnceLock::new(),
+            srgb_levels: OnceLock::new(),
+        }
+    }
+
+    /// Decodes an image file. Any pixel format the `image` crate knows is
+    /// accepted, including 16 bit and float ones. Gray images are read as if
+    /// all three channels were the same.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let image = Reader::open(path)
+            .map_err(|err| Error::image(path, err.into()))?
+            .decode()
+            .map_err(|err| Error::image(path, err))?;
+
+        Ok(MipMap::new(image))
+    }
+
+    /// The image as it was decoded.
+    pub fn image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    /// The number of levels, including the image itself. The last one is a
+    /// single pixel.
+    pub fn num_levels(&self) -> usize {
+        let (wd, ht) = self.image().dimensions();
+        (32 - wd.max(ht).leading_zeros()) as usize
+    }
+
+    /// The width and height of a level. Level 0 is the image itself. Levels
+    /// past the last one are clamped.
+    pub fn level_dimensions(&self, level: usize) -> (u32, u32) {
+        let level = level.min(self.num_levels() - 1);
+        let (wd, ht) = self.image().dimensions();
+        ((wd >> level).max(1), (ht >> level).max(1))
+    }
+
+    /// Makes the levels past the image itself for reading it in
+    /// `color_space`, unless they were made already.
+    pub fn make_levels(&self, color_space: ColorSpace) -> &[Rgb32FImage] {
+        let levels = match color_space {
+            ColorSpace::Linear => &self.linear_levels,
+            ColorSpace::Srgb => &self.srgb_levels,
+        };
+
+        levels.get_or_init(|| {
+            let mut smaller_levels: Vec<Rgb32FImage> = vec![];
+
+            for level in 1..self.num_levels() {
+                let (wd, ht) = self.level_dimensions(level);
+                let smaller = match smaller_levels.last() {
+                    Some(larger) => imageops::resize(larger, wd, ht, FilterType::Triangle),
+                    // Averages 2x2 blocks of the image, so that it isn't
+                    // copied at full resolution. Odd rows and columns at the
+                    // edges are left out, and a side of 1 pixel is repeated.
+                    None => {
+                        let (image_wd, image_ht) = self.image().dimensions();
+                        Rgb32FImage::from_fn(wd, ht, |x, y| {
+                            let block_sum = [(0, 0), (1, 0), (0, 1), (1, 1)].iter().fold(
+                                Vec3::ZERO,
+                                |sum, (dx, dy)| {
+                                    let texel_x = (2 * x + dx).min(image_wd - 1);
+                                    let texel_y = (2 * y + dy).min(image_ht - 1);
+                                    sum + self.texel(0, texel_x, texel_y, color_space)
+                                },
+                            );
+                            Rgb((block_sum / 4.0).into())
+                        })
+                    }
+                };
+                smaller_levels.push(smaller);
+            }
+
+            smaller_levels
+        })
+    }
+
+    /// The color space of images of this format, unless they're tagged with
+    /// another one. Float images are linear and the others are sRGB.
+    pub fn color_space(&self) -> ColorSpace {
+        match self.image {
+            DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => ColorSpace::Linear,
+            _ => ColorSpace::Srgb,
+        }
+    }
+
+    /// Returns the linear color of a pixel whose values are stored in
+    /// `color_space`. The channels of 8 and 16 bit images are in [0, 1].
+    /// Float images keep their full range. Levels past the last one are
+    /// clamped.
+    pub fn texel(&self, level: usize, x: u32, y: u32, color_space: ColorSpace) -> Vec3 {
+        if level > 0 {
+            let smaller_levels = self.make_levels(color_space);
+            return match smaller_levels
+                .get(level - 1)
+                .or_else(|| smaller_levels.last())
+            {
+                Some(level) => Vec3::from(level.get_pixel(x, y).0),
+                None => self.texel(0, x, y, color_space),
+            };
+        }
+
+        let decode_u16 = |Rgb([r, g, b]): Rgb<u16>| {
+            color_space.decode(Vec3::new(r as f32, g as f32, b as f32) / u16::MAX as f32)
+        };
+
+        match &self.image {
+            DynamicImage::ImageLuma16(image) => decode_u16(image.get_pixel(x, y).to_rgb()),
+            DynamicImage::ImageLumaA16(image) => decode_u16(image.get_pixel(x, y).to_rgb()),
+            DynamicImage::ImageRgb16(image) => decode_u16(*image.get_pixel(x, y)),
+            DynamicImage::ImageRgba16(image) => decode_u16(image.get_pixel(x, y).to_rgb()),
+            DynamicImage::ImageRgb32F(image) => {
+                color_space.decode(Vec3::from(image.get_pixel(x, y).0))
+            }
+            DynamicImage::ImageRgba32F(image) => {
+                color_space.decode(Vec3::from_slice(&image.get_pixel(x, y).0))
+            }
+            image => {
+                let [r, g, b, _] = image.get_pixel(x, y).0;
+                Vec3::new(
+                    color_space.decode_u8(r),
+                    color_space.decode_u8(g),
+                    color_space.decode_u8(b),
+                )
+            }
+        }
+    }
+
+    /// The memory taken by the pixels of the levels made so far.
+    pub fn bytes(&self) -> usize {
+        let level_bytes: usize = [&self.linear_levels, &self.srgb_levels]
+            .iter()
+            .filter_map(|levels| levels.get())
+            .flatten()
+            .map(|level| level.as_raw().len() * std::mem::size_of::<f32>())
+            .sum();
+
+        self.image.as_bytes().len() + level_bytes
+    }
+}
+
+impl From<DynamicImage> for MipMap {
+    fn from(image: DynamicImage) -> Self {
+        MipMap::new(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn levels_halve_down_to_a_single_pixel() {
+        let image = RgbImage::from_fn(8, 2, |x, _| Rgb([if x < 4 { 0 } else { 255 }; 3]));
+        let mipmap = MipMap::new(DynamicImage::ImageRgb8(image));
+
+        assert_eq!(4, mipmap.num_levels());
+        assert_eq!((4, 1), mipmap.level_dimensions(1));
+        assert_eq!((1, 1), mipmap.level_dimensions(3));
+        assert_eq!((1, 1), mipmap.level_dimensions(10));
+
+        // The last level averages the whole image.
+        assert!((mipmap.texel(3, 0, 0, ColorSpace::Linear).x - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn srgb_levels_average_linear_colors() {
+        let image = RgbImage::from_fn(2, 1, |x, _| Rgb([x as u8 * 255; 3]));
+        let mipmap = MipMap::new(DynamicImage::ImageRgb8(image));
+
+        // Half of the light of white, not the color stored halfway.
+        let texel = mipmap.texel(1, 0, 0, ColorSpace::Srgb);
+        assert!((texel.x - 0.5).abs() < 0.01, "{}", texel);
+    }
+
+    #[test]
+    fn texels_of_16_bit_and_float_images_keep_their_precision() {
+        let image = ImageBuffer::from_pixel(1, 1, Rgb([1000u16, 0, u16::MAX]));
+        let mipmap = MipMap::new(DynamicImage::ImageRgb16(image));
+
+        assert_eq!(ColorSpace::Srgb, mipmap.color_space());
+        let texel = mipmap.texel(0, 0, 0, ColorSpace::Linear);
+        assert_eq!(Vec3::new(1000.0 / 65535.0, 0.0, 1.0), texel);
+
+        let image = ImageBuffer::from_pixel(1, 1, Rgb([0.25f32, 4.0, 0.0]));
+        let mipmap = MipMap::new(DynamicImage::ImageRgb32F(image));
+
+        assert_eq!(ColorSpace::Linear, mipmap.color_space());
+        let texel = mipmap.texel(0, 0, 0, mipmap.color_space());
+        assert_eq!(Vec3::new(0.25, 4.0, 0.0), texel);
+    }
+}
@@ -1,27 +1,101 @@
 use glam::{Mat3, Vec3};
 use serde::{Deserialize, Serialize};
 
+use crate::cache::Cache;
+
+pub use self::checker::{Checker, Domain};
+pub use self::color_space::ColorSpace;
 pub use self::image::Image;
 pub use self::linear_gradient::LinearGradient;
+pub use self::marble::Marble;
+pub use self::mipmap::MipMap;
+pub use self::noise::Noise;
+pub use self::sampler::{Filter, Sampler, Wrap};
 pub use self::solid::Solid;
+pub use self::wood::Wood;
 
+mod checker;
+mod color_space;
 mod image;
 mod linear_gradient;
+mod marble;
+mod mipmap;
+mod noise;
+mod perlin;
+mod sampler;
 mod solid;
+mod wood;
 
 #[derive(Serialize, Deserialize)]
 pub enum Texture {
+    Checker(Checker),
     Image(Image),
     LinearGradient(LinearGradient),
+    Marble(Marble),
+    Noise(Noise),
     Solid(Solid),
+    Wood(Wood),
+}
+
+/// Where a texture is looked up.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TexCoord {
+    pub u: f32,
+    pub v: f32,
+    /// The width of the area that the lookup covers, in UV units. It's 0 for
+    /// lookups of a single point.
+    pub footprint: f32,
+    /// The looked up point in the scene space. Procedural textures like
+    /// `Noise` are evaluated there rather than at the UVs.
+    pub pos: Vec3,
+}
+
+impl TexCoord {
+    /// A lookup of a single point.
+    pub fn new(u: f32, v: f32) -> Self {
+        TexCoord {
+            u,
+            v,
+            footprint: 0.0,
+            pos: Vec3::ZERO,
+        }
+    }
 }
 
 impl Texture {
-    pub fn color(&self, u: f32, v: f32) -> Vec3 {
+    /// Returns the color at `coord`. Textures made of other textures look
+    /// them up in `texture_cache`.
+    pub fn color(&self, texture_cache: &Cache<Texture>, coord: TexCoord) -> Vec3 {
         match self {
-            Self::Image(image) => image.color(u, v),
-            Self::LinearGradient(gradient) => gradient.color(u),
+            Self::Checker(checker) => checker.color(texture_cache, coord),
+            Self::Image(image) => image.color(coord),
+            Self::LinearGradient(gradient) => gradient.color(coord.u),
+            Self::Marble(marble) => marble.color(coord),
+            Self::Noise(noise) => noise.color(coord),
             Self::Solid(solid) => solid.color,
+            Self::Wood(wood) => wood.color(coord),
+        }
+    }
+
+    /// The indices of the textures this texture is made of, along with the
+    /// fields that hold them.
+    pub fn texture_idxs(&self) -> Vec<(&'static str, usize)> {
+        match self {
+            Self::Checker(checker) => vec![
+                ("Checker.even_idx", checker.even_idx),
+                ("Checker.odd_idx", checker.odd_idx),
+            ],
+            _ => vec![],
+        }
+    }
+
+    /// Returns the values at `coord` for textures which hold data other than
+    /// colors (like normal maps or roughness maps). Images are read as linear
+    /// unless they're tagged otherwise.
+    pub fn data(&self, texture_cache: &Cache<Texture>, coord: TexCoord) -> Vec3 {
+        match self {
+            Self::Image(image) => image.data(coord),
+            _ => self.color(texture_cache, coord),
         }
     }
 
@@ -29,9 +103,11 @@ impl Texture {
     /// corresponding to the passed uv coordinates _in the scene space_.
     ///
     /// It takes in a change of basis matrix from TBN to scene space.
-    pub fn normal(&self, u: f32, v: f32, tbn: Mat3) -> Vec3 {
+    pub fn normal(&self, texture_cache: &Cache<Texture>, coord: TexCoord, tbn: Mat3) -> Vec3 {
+        let values = self.data(texture_cache, coord);
+
         // RGB values are in [0, 1]. Vectors are in [-1, 1].
-        let tbn_space_normal = self.color(u, v) * 2.0 - 1.0;
+        let tbn_space_normal = values * 2.0 - 1.0;
         (tbn * tbn_space_normal).normalize()
     }
 }
@@ -62,21 +138,33 @@ mod tests {
         // point facing us (normal along pos z-axis)
         let normal = Vec3::Z;
         let tbn = Tbn3::from_tn(Vec3::X, normal);
-        assert_eq!(normal, normal_map.normal(0.50, 0.5, tbn.matrix()));
+        assert_eq!(
+            normal,
+            normal_map.normal(&Cache::new(), TexCoord::new(0.50, 0.5), tbn.matrix())
+        );
 
         // point in the back (normal along neg z-axis)
         let normal = -Vec3::Z;
         let tbn = Tbn3::from_tn(-Vec3::X, normal);
-        assert_eq!(normal, normal_map.normal(0.00, 0.5, tbn.matrix()));
+        assert_eq!(
+            normal,
+            normal_map.normal(&Cache::new(), TexCoord::new(0.00, 0.5), tbn.matrix())
+        );
 
         // point on the right side (normal along pos x-axis)
         let normal = Vec3::X;
         let tbn = Tbn3::from_tn(-Vec3::Z, normal);
-        assert_eq!(normal, normal_map.normal(0.75, 0.5, tbn.matrix()));
+        assert_eq!(
+            normal,
+            normal_map.normal(&Cache::new(), TexCoord::new(0.75, 0.5), tbn.matrix())
+        );
 
         // point on the left side (normal along neg x-axis)
         let normal = -Vec3::X;
         let tbn = Tbn3::from_tn(Vec3::Z, normal);
-        assert_eq!(normal, normal_map.normal(0.25, 0.5, tbn.matrix()));
+        assert_eq!(
+            normal,
+            normal_map.normal(&Cache::new(), TexCoord::new(0.25, 0.5), tbn.matrix())
+        );
     }
 }
@@ -0,0 +1,82 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use super::perlin;
+use super::TexCoord;
+
+/// Blends between two colors with Perlin noise evaluated in the scene space.
+/// The noise changes about `scale` times per unit.
+#[derive(Serialize, Deserialize)]
+pub struct Noise {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub scale: f32,
+    /// The number of layers of finer and finer noise.
+    #[serde(default = "one")]
+    pub octaves: u32,
+    /// Adds up the absolute values of the layers instead, which looks like
+    /// smoke or clouds.
+    #[serde(default)]
+    pub turbulence: bool,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Noise {
+    pub fn color(&self, coord: TexCoord) -> Vec3 {
+        let pos = coord.pos * self.scale;
+
+        let t = if self.turbulence {
+            perlin::turbulence(pos, self.octaves)
+        } else {
+            (perlin::fractal(pos, self.octaves) + 1.0) / 2.0
+        };
+
+        self.from.lerp(self.to, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(turbulence: bool) -> Noise {
+        Noise {
+            from: Vec3::ZERO,
+            to: Vec3::ONE,
+            scale: 3.0,
+            octaves: 4,
+            turbulence,
+        }
+    }
+
+    fn at(pos: Vec3) -> TexCoord {
+        TexCoord {
+            pos,
+            ..TexCoord::new(0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn noise_is_halfway_on_the_lattice() {
+        // Every octave of the noise is 0 at these points.
+        let pos = Vec3::new(1.0, -2.0, 1.0 / 3.0);
+        assert_eq!(Vec3::splat(0.5), noise(false).color(at(pos)));
+        assert_eq!(Vec3::ZERO, noise(true).color(at(pos)));
+    }
+
+    #[test]
+    fn colors_stay_between_from_and_to() {
+        for turbulence in [false, true] {
+            let noise = noise(turbulence);
+
+            for i in 0..200 {
+                let pos = Vec3::new(i as f32 * 0.173, i as f32 * -0.041, i as f32 * 0.097);
+                let color = noise.color(at(pos));
+                assert!(color.cmpge(Vec3::ZERO).all() && color.cmple(Vec3::ONE).all());
+            }
+        }
+    }
+}
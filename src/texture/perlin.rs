@@ -0,0 +1,147 @@
+//! Ken Perlin's improved gradient noise and sums of it at finer and finer
+//! scales (octaves). Every scene gets the same noise, so renders don't change
+//! from run to run.
+
+use std::sync::OnceLock;
+
+use glam::Vec3;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+/// A shuffle of 0 to 255, repeated twice so that lookups of sums don't wrap.
+struct Permutation([u8; 512]);
+
+impl Permutation {
+    fn shared() -> &'static Permutation {
+        static PERMUTATION: OnceLock<Permutation> = OnceLock::new();
+
+        PERMUTATION.get_or_init(|| {
+            let mut shuffled: Vec<u8> = (0..=255).collect();
+            shuffled.shuffle(&mut Xoshiro256PlusPlus::seed_from_u64(0));
+
+            let mut table = [0; 512];
+            for (i, entry) in table.iter_mut().enumerate() {
+                *entry = shuffled[i % 256];
+            }
+            Permutation(table)
+        })
+    }
+
+    fn hash(&self, x: usize, y: usize, z: usize) -> u8 {
+        let Permutation(table) = self;
+        table[table[table[x] as usize + y] as usize + z]
+    }
+}
+
+/// Smooth noise in about [-1, 1]. It's 0 at points with integer coordinates
+/// and changes about once per unit.
+pub fn noise(pos: Vec3) -> f32 {
+    let cell = pos.floor();
+    let (x, y, z) = (
+        cell.x as i32 as usize & 255,
+        cell.y as i32 as usize & 255,
+        cell.z as i32 as usize & 255,
+    );
+    let local = pos - cell;
+    let weight = local * local * local * (local * (local * 6.0 - 15.0) + 10.0);
+
+    let permutation = Permutation::shared();
+    let corner = |dx: usize, dy: usize, dz: usize| {
+        let offset = Vec3::new(dx as f32, dy as f32, dz as f32);
+        gradient(permutation.hash(x + dx, y + dy, z + dz), local - offset)
+    };
+
+    let lerp = |a: f32, b: f32, t: f32| a + t * (b - a);
+    let front = lerp(
+        lerp(corner(0, 0, 0), corner(1, 0, 0), weight.x),
+        lerp(corner(0, 1, 0), corner(1, 1, 0), weight.x),
+        weight.y,
+    );
+    let back = lerp(
+        lerp(corner(0, 0, 1), corner(1, 0, 1), weight.x),
+        lerp(corner(0, 1, 1), corner(1, 1, 1), weight.x),
+        weight.y,
+    );
+    lerp(front, back, weight.z)
+}
+
+/// Dots `offset` with one of the 12 directions to the edges of a cube, picked
+/// by `hash`.
+fn gradient(hash: u8, offset: Vec3) -> f32 {
+    let hash = hash & 15;
+    let a = if hash < 8 { offset.x } else { offset.y };
+    let b = match hash {
+        0..=3 => offset.y,
+        12 | 14 => offset.x,
+        _ => offset.z,
+    };
+
+    let a = if hash & 1 == 0 { a } else { -a };
+    let b = if hash & 2 == 0 { b } else { -b };
+    a + b
+}
+
+/// Adds up `octaves` layers of noise, each twice as fine and half as strong as
+/// the one before. It's in about [-1, 1].
+pub fn fractal(pos: Vec3, octaves: u32) -> f32 {
+    octaves_of(pos, octaves)
+        .map(|(weight, pos)| weight * noise(pos))
+        .sum()
+}
+
+/// Like `fractal`, but adds up the absolute values of the layers. It's in
+/// about [0, 1] and has sharp creases where the layers cross 0.
+pub fn turbulence(pos: Vec3, octaves: u32) -> f32 {
+    octaves_of(pos, octaves)
+        .map(|(weight, pos)| weight * noise(pos).abs())
+        .sum()
+}
+
+/// Octaves past this many are left out. Their weights are too small to
+/// change an f32 sum.
+const MAX_OCTAVES: u32 = 16;
+
+/// Returns the weight and the scaled position of every octave. The weights
+/// add up to (almost) 1.
+fn octaves_of(pos: Vec3, octaves: u32) -> impl Iterator<Item = (f32, Vec3)> {
+    (0..octaves.clamp(1, MAX_OCTAVES)).map(move |octave| {
+        let scale = 2f32.powi(octave as i32);
+        (0.5 / scale, pos * scale)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_is_zero_on_the_lattice_and_bounded_elsewhere() {
+        for pos in [Vec3::ZERO, Vec3::new(3.0, -7.0, 12.0)] {
+            assert_eq!(0.0, noise(pos));
+        }
+
+        let values: Vec<f32> = (0..1000)
+            .map(|i| {
+                noise(Vec3::new(
+                    i as f32 * 0.137,
+                    i as f32 * 0.071,
+                    -(i as f32) * 0.053,
+                ))
+            })
+            .collect();
+
+        assert!(values.iter().all(|value| value.abs() <= 1.0));
+        assert!(values.iter().any(|value| value.abs() > 0.3));
+        assert!((0..1000).all(|i| turbulence(Vec3::splat(i as f32 * 0.31), 4) >= 0.0));
+    }
+
+    #[test]
+    fn octaves_past_the_maximum_are_left_out() {
+        let pos = Vec3::new(0.3, -1.7, 2.9);
+
+        assert!(fractal(pos, 64).is_finite());
+        assert_eq!(fractal(pos, MAX_OCTAVES), fractal(pos, 64));
+        assert_eq!(turbulence(pos, MAX_OCTAVES), turbulence(pos, u32::MAX));
+    }
+}
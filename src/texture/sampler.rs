@@ -0,0 +1,219 @@
+use glam::{Vec2, Vec3};
+use image::GenericImageView;
+use serde::{Deserialize, Serialize};
+
+use super::{ColorSpace, MipMap, TexCoord};
+
+/// Decides how an image texture is looked up.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sampler {
+    #[serde(default)]
+    pub filter: Filter,
+    #[serde(default)]
+    pub wrap: Wrap,
+    /// UVs are multiplied by `scale` and then moved by `offset` before the
+    /// lookup. A scale of 2 repeats the image twice across the surface.
+    #[serde(default = "one")]
+    pub scale: Vec2,
+    #[serde(default = "zero")]
+    pub offset: Vec2,
+}
+
+/// Textures which don't pick a filter use `Nearest`, like they did before
+/// samplers existed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Filter {
+    /// The color of the closest pixel.
+    #[default]
+    Nearest,
+    /// Blends the four closest pixels.
+    Bilinear,
+    /// Blends bilinear lookups of the two mipmap levels whose pixels are
+    /// closest in size to the lookup's footprint. Distant surfaces don't
+    /// shimmer with it.
+    Trilinear,
+}
+
+/// What's outside the image, whose UVs go from 0 to 1.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Wrap {
+    /// The image is tiled.
+    #[default]
+    Repeat,
+    /// The edges of the image are stretched.
+    Clamp,
+    /// The image is tiled, flipping every other tile.
+    Mirror,
+}
+
+fn one() -> Vec2 {
+    Vec2::ONE
+}
+
+fn zero() -> Vec2 {
+    Vec2::ZERO
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            filter: Filter::default(),
+            wrap: Wrap::default(),
+            scale: Vec2::ONE,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+impl Sampler {
+    /// Returns the linear color around `coord`. Pixels are decoded from
+    /// `color_space` before they're blended.
+    pub fn sample(&self, mipmap: &MipMap, color_space: ColorSpace, coord: TexCoord) -> Vec3 {
+        let uv = Vec2::new(coord.u, coord.v) * self.scale + self.offset;
+
+        match self.filter {
+            Filter::Nearest => {
+                let (wd, ht) = mipmap.image().dimensions();
+                let x = self.wrap.apply((uv.x * wd as f32).floor() as i64, wd);
+                let y = self.wrap.apply((uv.y * ht as f32).floor() as i64, ht);
+                mipmap.texel(0, x, y, color_space)
+            }
+            Filter::Bilinear => self.bilinear(mipmap, color_space, 0, uv),
+            Filter::Trilinear => {
+                let (wd, ht) = mipmap.image().dimensions();
+                let footprint = coord.footprint * self.scale.abs().max_element();
+                let max_lod = (mipmap.num_levels() - 1) as f32;
+                let lod = (footprint * wd.max(ht) as f32).log2().clamp(0.0, max_lod);
+
+                let level = lod.floor() as usize;
+                let color = self.bilinear(mipmap, color_space, level, uv);
+
+                if lod > level as f32 {
+                    let finer_weight = level as f32 + 1.0 - lod;
+                    color * finer_weight
+                        + self.bilinear(mipmap, color_space, level + 1, uv) * (1.0 - finer_weight)
+                } else {
+                    color
+                }
+            }
+        }
+    }
+
+    fn bilinear(&self, mipmap: &MipMap, color_space: ColorSpace, level: usize, uv: Vec2) -> Vec3 {
+        let (wd, ht) = mipmap.level_dimensions(level);
+        // Pixel centers are half a pixel away from the pixel's corner.
+        let x = uv.x * wd as f32 - 0.5;
+        let y = uv.y * ht as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+
+        let texel = |dx: i64, dy: i64| {
+            mipmap.texel(
+                level,
+                self.wrap.apply(x0 as i64 + dx, wd),
+                self.wrap.apply(y0 as i64 + dy, ht),
+                color_space,
+            )
+        };
+
+        let top = texel(0, 0) * (1.0 - tx) + texel(1, 0) * tx;
+        let bottom = texel(0, 1) * (1.0 - tx) + texel(1, 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+impl Wrap {
+    /// Maps the index of a pixel to one inside an image `len` pixels long.
+    fn apply(self, idx: i64, len: u32) -> u32 {
+        let len = len as i64;
+
+        let idx = match self {
+            Self::Repeat => idx.rem_euclid(len),
+            Self::Clamp => idx.clamp(0, len - 1),
+            Self::Mirror => {
+                let idx = idx.rem_euclid(2 * len);
+                if idx < len {
+                    idx
+                } else {
+                    2 * len - 1 - idx
+                }
+            }
+        };
+
+        idx as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    use super::*;
+
+    /// A 2x1 image which is black on the left and white on the right.
+    fn black_and_white() -> MipMap {
+        let image = RgbImage::from_fn(2, 1, |x, _| Rgb([x as u8 * 255; 3]));
+        MipMap::new(DynamicImage::ImageRgb8(image))
+    }
+
+    fn sample(sampler: &Sampler, u: f32, footprint: f32) -> f32 {
+        let coord = TexCoord {
+            u,
+            v: 0.5,
+            footprint,
+            ..TexCoord::default()
+        };
+        sampler
+            .sample(&black_and_white(), ColorSpace::Linear, coord)
+            .x
+    }
+
+    #[test]
+    fn wrap_modes_map_pixels_outside_the_image() {
+        let idxs = |wrap: Wrap| (-3..6).map(|idx| wrap.apply(idx, 3)).collect::<Vec<_>>();
+
+        assert_eq!(vec![0, 1, 2, 0, 1, 2, 0, 1, 2], idxs(Wrap::Repeat));
+        assert_eq!(vec![0, 0, 0, 0, 1, 2, 2, 2, 2], idxs(Wrap::Clamp));
+        assert_eq!(vec![2, 1, 0, 0, 1, 2, 2, 1, 0], idxs(Wrap::Mirror));
+    }
+
+    #[test]
+    fn bilinear_filter_blends_between_pixel_centers() {
+        let sampler = Sampler {
+            filter: Filter::Bilinear,
+            ..Sampler::default()
+        };
+
+        assert_eq!(0.0, sample(&sampler, 0.25, 0.0));
+        assert_eq!(0.5, sample(&sampler, 0.5, 0.0));
+        assert_eq!(1.0, sample(&sampler, 0.75, 0.0));
+    }
+
+    #[test]
+    fn scale_and_offset_move_the_uvs() {
+        let sampler = Sampler {
+            filter: Filter::Nearest,
+            scale: Vec2::splat(2.0),
+            offset: Vec2::new(0.5, 0.0),
+            ..Sampler::default()
+        };
+
+        // 0.2 * 2 + 0.5 lands in the right half and 0.3 * 2 + 0.5 wraps
+        // around to the left half.
+        assert_eq!(1.0, sample(&sampler, 0.2, 0.0));
+        assert_eq!(0.0, sample(&sampler, 0.3, 0.0));
+    }
+
+    #[test]
+    fn trilinear_filter_blurs_wide_footprints() {
+        let sampler = Sampler {
+            filter: Filter::Trilinear,
+            wrap: Wrap::Clamp,
+            ..Sampler::default()
+        };
+
+        assert_eq!(0.0, sample(&sampler, 0.25, 0.0));
+        // A footprint as wide as the image uses the single pixel level.
+        assert!((sample(&sampler, 0.25, 1.0) - 0.5).abs() < 0.01);
+    }
+}
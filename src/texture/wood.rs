@@ -0,0 +1,72 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use super::perlin;
+use super::TexCoord;
+
+/// Growth rings around the y axis of the scene space, made uneven with noise.
+/// There are about `scale` rings per unit. Each ring fades from `from` at
+/// its inside to `to` at its outside.
+#[derive(Serialize, Deserialize)]
+pub struct Wood {
+    pub from: Vec3,
+    pub to: Vec3,
+    pub scale: f32,
+    /// How far the rings are pushed around by the noise.
+    pub distortion: f32,
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+}
+
+fn default_octaves() -> u32 {
+    3
+}
+
+impl Wood {
+    pub fn color(&self, coord: TexCoord) -> Vec3 {
+        let pos = coord.pos * self.scale;
+        let rings = pos.x.hypot(pos.z) + self.distortion * perlin::fractal(pos, self.octaves);
+
+        self.from.lerp(self.to, rings.rem_euclid(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPS: f32 = 0.0001;
+
+    fn at(pos: Vec3) -> TexCoord {
+        TexCoord {
+            pos,
+            ..TexCoord::new(0.0, 0.0)
+        }
+    }
+
+    #[test]
+    fn undistorted_rings_are_circles_around_y() {
+        let wood = Wood {
+            from: Vec3::ZERO,
+            to: Vec3::ONE,
+            scale: 2.0,
+            distortion: 0.0,
+            octaves: 3,
+        };
+
+        // 0.625 from the axis is a quarter of the way through the second ring.
+        for pos in [
+            Vec3::new(0.625, 0.0, 0.0),
+            Vec3::new(0.0, 5.0, -0.625),
+            Vec3::new(0.375, -2.0, 0.5),
+        ] {
+            let color = wood.color(at(pos));
+            assert!(
+                color.abs_diff_eq(Vec3::splat(0.25), EPS),
+                "{} at {}",
+                color,
+                pos
+            );
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::convert::TryFrom;
+
+use glam::{Mat3, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::bvh::Aabb;
+use crate::geometry::{HitInfo, SurfaceSample, Tbn3};
+use crate::primitive::Ray3;
+
+/// An affine transform which places an item in the scene. The item's geometry
+/// is scaled first, then rotated and then translated.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "TransformInitOptions", into = "TransformInitOptions")]
+pub struct Transform {
+    object_to_world: Mat4,
+    world_to_object: Mat4,
+    /// Transforms normals from the object space to the scene space.
+    normal_matrix: Mat3,
+    init_options: TransformInitOptions,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TransformInitOptions {
+    #[serde(default = "zero")]
+    pub translation: Vec3,
+    #[serde(default)]
+    pub rotation: Rotation,
+    #[serde(default = "one")]
+    pub scale: Vec3,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum Rotation {
+    /// Angles (in degrees) about the x, y and z axes. The rotations are applied
+    /// in that order.
+    Euler(Vec3),
+    Quat(Quat),
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::Quat(Quat::IDENTITY)
+    }
+}
+
+impl Rotation {
+    fn quat(&self) -> Quat {
+        match self {
+            Self::Euler(angles) => {
+                Quat::from_rotation_z(angles.z.to_radians())
+                    * Quat::from_rotation_y(angles.y.to_radians())
+                    * Quat::from_rotation_x(angles.x.to_radians())
+            }
+            Self::Quat(quat) => quat.normalize(),
+        }
+    }
+}
+
+fn zero() -> Vec3 {
+    Vec3::ZERO
+}
+
+fn one() -> Vec3 {
+    Vec3::ONE
+}
+
+impl Transform {
+    /// Moves the ray into the object space. Its direction isn't normalized so
+    /// that distances along it match the ones along the original ray.
+    pub fn ray_to_object(&self, ray: &Ray3) -> Ray3 {
+        Ray3::at_time(
+            self.world_to_object.transform_point3(ray.pos),
+            self.world_to_object.transform_vector3(ray.dir),
+            ray.time,
+        )
+    }
+
+    /// Moves a hit found using `ray_to_object` back into the scene space.
+    pub fn hit_to_world(&self, hit: HitInfo) -> HitInfo {
+        let n = self.normal_matrix * hit.tbn.n;
+        let t = self.object_to_world.transform_vector3(hit.tbn.t);
+
+        let tbn = Tbn3::from_tn(t, n);
+
+        HitInfo {
+            pos: self.object_to_world.transform_point3(hit.pos),
+            uv_density: hit.uv_density / self.area_scale(tbn.n),
+            tbn,
+            ..hit
+        }
+    }
+
+    /// Moves a point picked on the geometry's surface into the scene space.
+    /// Its probability density is adjusted for the change in area.
+    pub fn sample_to_world(&self, sample: SurfaceSample) -> SurfaceSample {
+        let normal = (self.normal_matrix * sample.normal).normalize();
+
+        SurfaceSample {
+            pos: self.object_to_world.transform_point3(sample.pos),
+            normal,
+            pdf: sample.pdf / self.area_scale(normal),
+        }
+    }
+
+    /// Returns how much a small patch of the surface with the given normal
+    /// (in the scene space) grows when it's moved into the scene space.
+    pub fn area_scale(&self, normal: Vec3) -> f32 {
+        let linear = Mat3::from_mat4(self.object_to_world);
+        linear.determinant().abs() / (linear.transpose() * normal).length()
+    }
+
+    /// Returns a box in the scene space which encloses the transformed box.
+    pub fn aabb_to_world(&self, aabb: &Aabb) -> Aabb {
+        let mut world_aabb = Aabb::EMPTY;
+
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { aabb.min.x } else { aabb.max.x },
+                if i & 2 == 0 { aabb.min.y } else { aabb.max.y },
+                if i & 4 == 0 { aabb.min.z } else { aabb.max.z },
+            );
+            world_aabb = world_aabb.grow(self.object_to_world.transform_point3(corner));
+        }
+
+        world_aabb
+    }
+}
+
+/// Fails if the scale flattens the item, since such a transform can't be
+/// undone to move rays into the object space.
+impl TryFrom<TransformInitOptions> for Transform {
+    type Error = String;
+
+    fn try_from(options: TransformInitOptions) -> Result<Self, Self::Error> {
+        let object_to_world = Mat4::from_scale_rotation_translation(
+            options.scale,
+            options.rotation.quat(),
+            options.translation,
+        );
+        let world_to_object = object_to_world.inverse();
+
+        if options.scale.cmpeq(Vec3::ZERO).any() || !world_to_object.is_finite() {
+            return Err(format!(
+                "Transform scale {} can't be inverted",
+                options.scale
+            ));
+        }
+
+        Ok(Transform {
+            object_to_world,
+            world_to_object,
+            normal_matrix: Mat3::from_mat4(object_to_world).inverse().transpose(),
+            init_options: options,
+        })
+    }
+}
+
+impl From<Transform> for TransformInitOptions {
+    fn from(transform: Transform) -> Self {
+        transform.init_options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::geometry::Sphere;
+
+    use super::*;
+
+    const EPS: f32 = 0.0001;
+
+    fn transform(translation: Vec3, rotation: Rotation, scale: Vec3) -> Transform {
+        TransformInitOptions {
+            translation,
+            rotation,
+            scale,
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    fn unit_sphere() -> Sphere {
+        Sphere {
+            center: Vec3::ZERO,
+            radius: 1.0,
+        }
+    }
+
+    fn transformed_hit(transform: &Transform, sphere: &Sphere, ray: &Ray3) -> HitInfo {
+        let hit = sphere
+            .hit(&transform.ray_to_object(ray), 0.0, f32::MAX)
+            .unwrap();
+        transform.hit_to_world(hit)
+    }
+
+    #[test]
+    fn translated_hit_is_moved_back_to_the_scene() {
+        let transform = transform(Vec3::new(5.0, 0.0, 0.0), Rotation::default(), Vec3::ONE);
+
+        let ray = Ray3::new(Vec3::new(5.0, 0.0, 3.0), -Vec3::Z);
+        let hit = transformed_hit(&transform, &unit_sphere(), &ray);
+
+        assert_eq!(2.0, hit.t);
+        assert_eq!(Vec3::new(5.0, 0.0, 1.0), hit.pos);
+        assert_eq!(Vec3::Z, hit.tbn.n);
+    }
+
+    #[test]
+    fn scaled_hit_has_correct_t_and_normal() {
+        // An ellipsoid with a radius of 2 along x.
+        let transform = transform(Vec3::ZERO, Rotation::default(), Vec3::new(2.0, 1.0, 1.0));
+
+        let ray = Ray3::new(Vec3::new(4.0, 0.0, 0.0), -Vec3::X);
+        let hit = transformed_hit(&transform, &unit_sphere(), &ray);
+        assert!((hit.t - 2.0).abs() < EPS);
+
+        // The normal of an ellipsoid isn't the direction from its center.
+        let ray = Ray3::new(Vec3::new(2.0f32.sqrt(), 5.0, 0.0), -Vec3::Y);
+        let hit = transformed_hit(&transform, &unit_sphere(), &ray);
+        let expected_normal = Vec3::new(hit.pos.x / 4.0, hit.pos.y, 0.0).normalize();
+        assert!(hit.tbn.n.abs_diff_eq(expected_normal, EPS));
+        assert!(hit.tbn.t.dot(hit.tbn.n).abs() < EPS);
+    }
+
+    #[test]
+    fn euler_rotation_is_applied_in_xyz_order() {
+        let transform = transform(
+            Vec3::ZERO,
+            Rotation::Euler(Vec3::new(90.0, 90.0, 0.0)),
+            Vec3::ONE,
+        );
+
+        // x rotates y onto z and then y rotates z onto x.
+        let pos = transform.object_to_world.transform_vector3(Vec3::Y);
+        assert!(pos.abs_diff_eq(Vec3::X, EPS));
+    }
+
+    #[test]
+    fn aabb_to_world_encloses_rotated_box() {
+        let transform = transform(
+            Vec3::new(0.0, 1.0, 0.0),
+            Rotation::Euler(Vec3::new(0.0, 45.0, 0.0)),
+            Vec3::ONE,
+        );
+        let aabb = transform.aabb_to_world(&Aabb {
+            min: -Vec3::ONE,
+            max: Vec3::ONE,
+        });
+
+        let half_diagonal = 2.0f32.sqrt();
+        assert!(aabb
+            .min
+            .abs_diff_eq(Vec3::new(-half_diagonal, 0.0, -half_diagonal), EPS));
+        assert!(aabb
+            .max
+            .abs_diff_eq(Vec3::new(half_diagonal, 2.0, half_diagonal), EPS));
+    }
+
+    #[test]
+    fn area_scale_depends_on_the_orientation_of_the_patch() {
+        let transform = transform(Vec3::ZERO, Rotation::default(), Vec3::new(2.0, 3.0, 1.0));
+
+        assert!((transform.area_scale(Vec3::Z) - 6.0).abs() < EPS);
+        assert!((transform.area_scale(Vec3::X) - 3.0).abs() < EPS);
+    }
+
+    #[test]
+    fn flattening_scale_is_rejected() {
+        let err = serde_json::from_str::<Transform>(r#"{"scale": [1, 0, 1]}"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("can't be inverted"), "{}", err);
+    }
+
+    #[test]
+    fn missing_init_options_fall_back_to_identity() {
+        let transform: Transform = serde_json::from_str("{}").unwrap();
+        assert_eq!(Mat4::IDENTITY, transform.object_to_world);
+    }
+}
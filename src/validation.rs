@@ -0,0 +1,540 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use crate::cache::Cache;
+use crate::geometry::Geometry;
+use crate::item::Item;
+use crate::material::Material;
+use crate::sky_box::SkyBox;
+use crate::texture::Texture;
+
+/// An index in a scene which doesn't point to anything in its cache.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidIndex {
+    /// Where the index is in the scene's JSON, like
+    /// `material_cache.items[2].Metal.texture_idx`.
+    pub path: String,
+    /// The name of the cache entry which holds the index. Items and sky boxes
+    /// have no names.
+    pub name: Option<String>,
+    pub idx: usize,
+    /// The cache the index points into, like `texture_cache`.
+    pub cache: &'static str,
+    pub cache_len: usize,
+}
+
+/// A name in a scene which doesn't belong to anything in its cache.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnknownName {
+    /// Where the name is in the scene's JSON.
+    pub path: String,
+    pub name: String,
+    /// The cache the name was looked up in.
+    pub cache: &'static str,
+}
+
+/// An index which makes a cache entry (like a `Checker` texture) part of
+/// itself, either directly or through other entries.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cycle {
+    /// Where the index which closes the loop is in the scene's JSON.
+    pub path: String,
+    pub name: Option<String>,
+    pub cache: &'static str,
+}
+
+/// Everything that's wrong with the references of a scene.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValidationError {
+    pub unknown_names: Vec<UnknownName>,
+    pub invalid_indices: Vec<InvalidIndex>,
+    pub cycles: Vec<Cycle>,
+}
+
+impl Display for InvalidIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(name) = &self.name {
+            write!(f, " (of {:?})", name)?;
+        }
+        write!(
+            f,
+            " is {} but {} has {} entries",
+            self.idx, self.cache, self.cache_len
+        )
+    }
+}
+
+impl Display for UnknownName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} is {:?} but {} has no such entry",
+            self.path, self.name, self.cache
+        )
+    }
+}
+
+impl Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path)?;
+        if let Some(name) = &self.name {
+            write!(f, " (of {:?})", name)?;
+        }
+        write!(f, " makes a loop in {}", self.cache)
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scene has invalid references:")?;
+        for unknown_name in &self.unknown_names {
+            write!(f, "\n  {}", unknown_name)?;
+        }
+        for invalid_index in &self.invalid_indices {
+            write!(f, "\n  {}", invalid_index)?;
+        }
+        for cycle in &self.cycles {
+            write!(f, "\n  {}", cycle)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Checks that every index in the scene points to an entry of its cache and
+/// that no texture or material is made of itself.
+pub(crate) fn validate(
+    sky_box: &SkyBox,
+    texture_cache: &Cache<Texture>,
+    geometry_cache: &Cache<Geometry>,
+    material_cache: &Cache<Material>,
+    items: &[Item],
+) -> Result<(), ValidationError> {
+    let mut invalid_indices = vec![];
+
+    let textures = ("texture_cache", texture_cache.len());
+    let geometries = ("geometry_cache", geometry_cache.len());
+    let materials = ("material_cache", material_cache.len());
+
+    let mut check = |path: String, name: Option<&str>, idx: usize, cache: (&'static str, usize)| {
+        let (cache, cache_len) = cache;
+
+        if idx >= cache_len {
+            invalid_indices.push(InvalidIndex {
+                path,
+                name: name.map(str::to_owned),
+                idx,
+                cache,
+                cache_len,
+            });
+        }
+    };
+
+    for (field, idx) in sky_box_texture_idxs(sky_box) {
+        check(format!("sky_box.{}", field), None, idx, textures);
+    }
+
+    for (texture_idx, texture) in texture_cache.iter().enumerate() {
+        let name = texture_cache.name_of(texture_idx);
+
+        for (field, idx) in texture.texture_idxs() {
+            let path = format!("texture_cache.items[{}].{}", texture_idx, field);
+            check(path, name, idx, textures);
+        }
+    }
+
+    for (material_idx, material) in material_cache.iter().enumerate() {
+        let name = material_cache.name_of(material_idx);
+
+        for (field, idx) in material_texture_idxs(material) {
+            let path = format!("material_cache.items[{}].{}", material_idx, field);
+            check(path, name, idx, textures);
+        }
+
+        for (field, idx) in material.material_idxs() {
+            let path = format!("material_cache.items[{}].{}", material_idx, field);
+            check(path, name, idx, materials);
+        }
+    }
+
+    for (item_idx, item) in items.iter().enumerate() {
+        let path = |field| format!("items[{}].{}", item_idx, field);
+        check(path("geometry_idx"), None, item.geometry_idx, geometries);
+        check(path("material_idx"), None, item.material_idx, materials);
+    }
+
+    let mut cycles: Vec<Cycle> =
+        find_cycles(texture_cache.len(), |idx| texture_cache[idx].texture_idxs())
+            .into_iter()
+            .map(|(texture_idx, field)| Cycle {
+                path: format!("texture_cache.items[{}].{}", texture_idx, field),
+                name: texture_cache.name_of(texture_idx).map(str::to_owned),
+                cache: "texture_cache",
+            })
+            .collect();
+    cycles.extend(
+        find_cycles(material_cache.len(), |idx| {
+            material_cache[idx].material_idxs()
+        })
+        .into_iter()
+        .map(|(material_idx, field)| Cycle {
+            path: format!("material_cache.items[{}].{}", material_idx, field),
+            name: material_cache.name_of(material_idx).map(str::to_owned),
+            cache: "material_cache",
+        }),
+    );
+
+    if invalid_indices.is_empty() && cycles.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError {
+            invalid_indices,
+            cycles,
+            ..Default::default()
+        })
+    }
+}
+
+/// Returns the entries of a cache which refer to an entry that (directly or
+/// indirectly) refers to them, along with the field that does. `refs` returns
+/// the fields and indices an entry refers to. Indices out of range are
+/// ignored, since they're reported on their own.
+fn find_cycles<F>(len: usize, refs: F) -> Vec<(usize, &'static str)>
+where
+    F: Fn(usize) -> Vec<(&'static str, usize)>,
+{
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        Unvisited,
+        /// Its references are being followed.
+        Open,
+        Done,
+    }
+
+    fn visit<F>(idx: usize, refs: &F, states: &mut [State], cycles: &mut Vec<(usize, &'static str)>)
+    where
+        F: Fn(usize) -> Vec<(&'static str, usize)>,
+    {
+        states[idx] = State::Open;
+
+        for (field, ref_idx) in refs(idx) {
+            match states.get(ref_idx) {
+                Some(State::Unvisited) => visit(ref_idx, refs, states, cycles),
+                Some(State::Open) => cycles.push((idx, field)),
+                Some(State::Done) | None => {}
+            }
+        }
+
+        states[idx] = State::Done;
+    }
+
+    let mut states = vec![State::Unvisited; len];
+    let mut cycles = vec![];
+
+    for idx in 0..len {
+        if states[idx] == State::Unvisited {
+            visit(idx, &refs, &mut states, &mut cycles);
+        }
+    }
+
+    cycles
+}
+
+fn sky_box_texture_idxs(sky_box: &SkyBox) -> Vec<(&'static str, usize)> {
+    match sky_box {
+        SkyBox::Cubemap(cubemap) => vec![
+            ("Cubemap.up_idx", cubemap.up_idx),
+            ("Cubemap.dn_idx", cubemap.dn_idx),
+            ("Cubemap.lf_idx", cubemap.lf_idx),
+            ("Cubemap.rt_idx", cubemap.rt_idx),
+            ("Cubemap.ft_idx", cubemap.ft_idx),
+            ("Cubemap.bk_idx", cubemap.bk_idx),
+        ],
+        SkyBox::Equirectangular(equirectangular) => {
+            vec![("Equirectangular.tex_idx", equirectangular.tex_idx)]
+        }
+    }
+}
+
+fn material_texture_idxs(material: &Material) -> Vec<(&'static str, usize)> {
+    let mut idxs = vec![];
+
+    match material {
+        Material::Coated(_) => {}
+        Material::Dielectric(dielectric) => {
+            idxs.extend(
+                dielectric
+                    .normal_map_idx
+                    .map(|idx| ("Dielectric.normal_map_idx", idx)),
+            );
+            idxs.extend(
+                dielectric
+                    .emission
+                    .as_ref()
+                    .map(|emission| ("Dielectric.emission.texture_idx", emission.texture_idx)),
+            );
+        }
+        Material::Lambertian(lambertian) => {
+            idxs.push(("Lambertian.texture_idx", lambertian.texture_idx));
+            idxs.extend(
+                lambertian
+                    .normal_map_idx
+                    .map(|idx| ("Lambertian.normal_map_idx", idx)),
+            );
+            idxs.extend(
+                lambertian
+                    .emission
+                    .as_ref()
+                    .map(|emission| ("Lambertian.emission.texture_idx", emission.texture_idx)),
+            );
+        }
+        Material::Light(light) => idxs.push(("Light.texture_idx", light.texture_idx)),
+        Material::Metal(metal) => {
+            idxs.push(("Metal.texture_idx", metal.texture_idx));
+            idxs.extend(
+                metal
+                    .normal_map_idx
+                    .map(|idx| ("Metal.normal_map_idx", idx)),
+            );
+            idxs.extend(
+                metal
+                    .emission
+                    .as_ref()
+                    .map(|emission| ("Metal.emission.texture_idx", emission.texture_idx)),
+            );
+        }
+        Material::Mix(mix) => idxs.extend(mix.mask_idx.map(|idx| ("Mix.mask_idx", idx))),
+        Material::Principled(principled) => {
+            idxs.push(("Principled.base_color_idx", principled.base_color_idx));
+            idxs.extend(
+                principled
+                    .normal_map_idx
+                    .map(|idx| ("Principled.normal_map_idx", idx)),
+            );
+            idxs.extend(
+                principled
+                    .metallic_idx
+                    .map(|idx| ("Principled.metallic_idx", idx)),
+            );
+            idxs.extend(
+                principled
+                    .roughness_idx
+                    .map(|idx| ("Principled.roughness_idx", idx)),
+            );
+            idxs.extend(
+                principled
+                    .emission
+                    .as_ref()
+                    .map(|emission| ("Principled.emission.texture_idx", emission.texture_idx)),
+            );
+        }
+    }
+
+    idxs
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use crate::geometry::Sphere;
+    use crate::material::{Coated, Lambertian, Metal, Mix};
+    use crate::sky_box::Cubemap;
+    use crate::texture::{Checker, Domain, Solid};
+
+    use super::*;
+
+    #[test]
+    fn every_invalid_index_is_reported() {
+        let mut texture_cache = Cache::new();
+        texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+
+        let mut geometry_cache = Cache::new();
+        geometry_cache.add(
+            "ball",
+            Geometry::Sphere(Sphere {
+                center: Vec3::ZERO,
+                radius: 1.0,
+            }),
+        );
+
+        let mut material_cache = Cache::new();
+        material_cache.add(
+            "matte",
+            Material::Lambertian(Lambertian {
+                texture_idx: 0,
+                normal_map_idx: None,
+                emission: None,
+            }),
+        );
+        material_cache.add(
+            "shiny",
+            Material::Metal(Metal {
+                texture_idx: 0,
+                normal_map_idx: Some(3),
+                fuzz: 0.0,
+                emission: None,
+            }),
+        );
+
+        let sky_box = SkyBox::Cubemap(Cubemap {
+            bk_idx: 1,
+            ..Cubemap::all(0)
+        });
+        let items = [
+            Item {
+                geometry_idx: 0,
+                material_idx: 1,
+                transform: None,
+            },
+            Item {
+                geometry_idx: 1,
+                material_idx: 2,
+                transform: None,
+            },
+        ];
+
+        let err = validate(
+            &sky_box,
+            &texture_cache,
+            &geometry_cache,
+            &material_cache,
+            &items,
+        )
+        .unwrap_err();
+
+        let paths: Vec<&str> = err
+            .invalid_indices
+            .iter()
+            .map(|invalid_index| invalid_index.path.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "sky_box.Cubemap.bk_idx",
+                "material_cache.items[1].Metal.normal_map_idx",
+                "items[1].geometry_idx",
+                "items[1].material_idx",
+            ],
+            paths
+        );
+        assert_eq!(
+            "material_cache.items[1].Metal.normal_map_idx (of \"shiny\") is 3 but texture_cache has 1 entries",
+            err.invalid_indices[1].to_string()
+        );
+    }
+
+    #[test]
+    fn textures_made_of_themselves_are_reported() {
+        let checker = |even_idx, odd_idx| {
+            Texture::Checker(Checker {
+                even_idx,
+                odd_idx,
+                scale: 1.0,
+                domain: Domain::Uv,
+            })
+        };
+
+        let mut texture_cache = Cache::new();
+        texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+        texture_cache.add("board", checker(0, 2));
+        texture_cache.add("nested", checker(1, 0));
+        texture_cache.add("fine", checker(0, 0));
+
+        let err = validate(
+            &SkyBox::Cubemap(Cubemap::all(3)),
+            &texture_cache,
+            &Cache::new(),
+            &Cache::new(),
+            &[],
+        )
+        .unwrap_err();
+
+        assert!(err.invalid_indices.is_empty());
+        assert_eq!(
+            vec![Cycle {
+                path: "texture_cache.items[2].Checker.even_idx".to_owned(),
+                name: Some("nested".to_owned()),
+                cache: "texture_cache",
+            }],
+            err.cycles
+        );
+    }
+
+    #[test]
+    fn materials_made_of_themselves_are_reported() {
+        let mut texture_cache = Cache::new();
+        texture_cache.add("white", Texture::Solid(Solid { color: Vec3::ONE }));
+
+        let mut material_cache = Cache::new();
+        material_cache.add(
+            "matte",
+            Material::Lambertian(Lambertian {
+                texture_idx: 0,
+                normal_map_idx: None,
+                emission: None,
+            }),
+        );
+        material_cache.add(
+            "rusty",
+            Material::Mix(Mix {
+                base_idx: 0,
+                layer_idx: 2,
+                amount: 0.5,
+                mask_idx: Some(1),
+            }),
+        );
+        material_cache.add(
+            "varnished",
+            Material::Coated(Coated {
+                base_idx: 1,
+                ref_idx: 1.5,
+                roughness: 0.0,
+            }),
+        );
+
+        let err = validate(
+            &SkyBox::Cubemap(Cubemap::all(0)),
+            &texture_cache,
+            &Cache::new(),
+            &material_cache,
+            &[],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            vec!["material_cache.items[1].Mix.mask_idx"],
+            err.invalid_indices
+                .iter()
+                .map(|invalid_index| invalid_index.path.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Cycle {
+                path: "material_cache.items[2].Coated.base_idx".to_owned(),
+                name: Some("varnished".to_owned()),
+                cache: "material_cache",
+            }],
+            err.cycles
+        );
+    }
+
+    #[test]
+    fn loading_a_scene_with_an_invalid_index_fails() {
+        let json = r#"{
+            "sky_box": {"Equirectangular": {"tex_idx": 0}},
+            "camera": {"pos": [0, 0, 1], "look_at": [0, 0, 0], "vup": [0, 1, 0], "vt_fov": 40, "aspect": 2},
+            "texture_cache": {"idxs": {"sky": 0}, "items": [{"Solid": {"color": [1, 1, 1]}}]},
+            "geometry_cache": {"idxs": {}, "items": []},
+            "material_cache": {"idxs": {}, "items": []},
+            "items": [{"geometry_idx": 0, "material_idx": 0}]
+        }"#;
+
+        let err = serde_json::from_str::<crate::scene::Scene>(json)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("items[0].geometry_idx is 0 but geometry_cache has 0 entries"));
+    }
+}